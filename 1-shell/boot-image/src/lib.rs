@@ -0,0 +1,303 @@
+#![no_std]
+
+pub mod lz4;
+#[cfg(feature = "signatures")]
+pub mod sign;
+#[cfg(test)]
+mod tests;
+
+/// Bytes identifying a boot image header: `PIMG`.
+pub const MAGIC: [u8; 4] = *b"PIMG";
+
+/// Version of the header layout produced by this crate.
+pub const VERSION: u8 = 1;
+
+/// Size of the boot image header, in bytes.
+pub const HEADER_LEN: usize = 16;
+
+/// Size of the Ed25519 signature trailing a signed image's payload, in bytes.
+pub const SIGNATURE_LEN: usize = 64;
+
+/// Header flag set when a signature follows the payload.
+pub const FLAG_SIGNED: u16 = 1 << 0;
+
+/// Header flag marking an image as an initial ramdisk rather than a kernel.
+pub const FLAG_RAMDISK: u16 = 1 << 1;
+
+/// Errors that can occur while packing or unpacking a boot image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The header is malformed or has an unknown version.
+    BadHeader,
+    /// The header names a compression method this crate doesn't know.
+    UnsupportedCompression(u8),
+    /// The data ended before the payload announced by the header.
+    Truncated,
+    /// The output buffer is too small to hold the result.
+    TooLarge,
+    /// The compressed payload is not a valid stream.
+    Corrupt,
+    /// The unpacked image length differs from the header's `image_len`.
+    LengthMismatch,
+    /// A signature was required but the image isn't signed.
+    Unsigned,
+    /// The image's signature doesn't match its contents or key.
+    BadSignature,
+    /// The signing or verification key is invalid.
+    BadKey,
+}
+
+/// Compression method used for a boot image's payload.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    None = 0,
+    Lz4 = 1,
+}
+
+impl Compression {
+    fn from_u8(value: u8) -> Result<Compression, Error> {
+        match value {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Lz4),
+            other => Err(Error::UnsupportedCompression(other)),
+        }
+    }
+}
+
+/// The header prepended to a packed boot image.
+///
+/// All fields are stored little-endian in the following layout:
+///
+/// ```text
+/// 0       4         5             6       8             12          16
+/// | magic | version | compression | flags | payload_len | image_len |
+/// ```
+///
+/// The payload follows the header. If `FLAG_SIGNED` is set, a
+/// `SIGNATURE_LEN`-byte Ed25519 signature over the header and payload follows
+/// the payload.
+///
+/// Several packed images may be sent back to back, e.g. a kernel followed by
+/// a ramdisk; see `images`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub compression: Compression,
+    pub flags: u16,
+    /// Number of payload bytes following the header.
+    pub payload_len: u32,
+    /// Number of bytes the payload unpacks to.
+    pub image_len: u32,
+}
+
+impl Header {
+    /// Returns `true` if `data` begins with the boot image magic.
+    pub fn is_present(data: &[u8]) -> bool {
+        data.len() >= MAGIC.len() && data[..MAGIC.len()] == MAGIC
+    }
+
+    /// Parses the header at the start of `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BadHeader` if `data` doesn't start with a valid header and
+    /// `UnsupportedCompression` if the compression method is unknown.
+    pub fn parse(data: &[u8]) -> Result<Header, Error> {
+        if data.len() < HEADER_LEN || !Header::is_present(data) || data[4] != VERSION {
+            return Err(Error::BadHeader);
+        }
+
+        Ok(Header {
+            compression: Compression::from_u8(data[5])?,
+            flags: u16::from(data[6]) | (u16::from(data[7]) << 8),
+            payload_len: read_u32(&data[8..12]),
+            image_len: read_u32(&data[12..16]),
+        })
+    }
+
+    /// Returns `true` if a signature follows the payload.
+    pub fn is_signed(&self) -> bool {
+        self.flags & FLAG_SIGNED != 0
+    }
+
+    /// Returns `true` if the image is an initial ramdisk.
+    pub fn is_ramdisk(&self) -> bool {
+        self.flags & FLAG_RAMDISK != 0
+    }
+
+    /// Returns the number of bytes the packed image occupies: the header, the
+    /// payload and, if present, the signature.
+    pub fn packed_len(&self) -> usize {
+        let signature_len = if self.is_signed() { SIGNATURE_LEN } else { 0 };
+        HEADER_LEN + self.payload_len as usize + signature_len
+    }
+
+    /// Serializes `self` into its on-the-wire representation.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = self.compression as u8;
+        bytes[6] = self.flags as u8;
+        bytes[7] = (self.flags >> 8) as u8;
+        write_u32(&mut bytes[8..12], self.payload_len);
+        write_u32(&mut bytes[12..16], self.image_len);
+        bytes
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0])
+        | (u32::from(bytes[1]) << 8)
+        | (u32::from(bytes[2]) << 16)
+        | (u32::from(bytes[3]) << 24)
+}
+
+fn write_u32(bytes: &mut [u8], value: u32) {
+    bytes[0] = value as u8;
+    bytes[1] = (value >> 8) as u8;
+    bytes[2] = (value >> 16) as u8;
+    bytes[3] = (value >> 24) as u8;
+}
+
+/// Returns an upper bound on the size of `image_len` bytes packed with
+/// `compression`, header included.
+pub fn max_packed_len(image_len: usize, compression: Compression) -> usize {
+    HEADER_LEN + match compression {
+        Compression::None => image_len,
+        Compression::Lz4 => lz4::max_compressed_len(image_len),
+    }
+}
+
+/// Packs `image` into `out` as a header followed by the payload compressed
+/// with `compression`. Returns the number of bytes written to `out`.
+///
+/// # Errors
+///
+/// Returns `TooLarge` if `out` is smaller than needed or if `image` doesn't
+/// fit in the header's 32-bit length fields.
+pub fn pack(image: &[u8], compression: Compression, out: &mut [u8]) -> Result<usize, Error> {
+    pack_with_flags(image, compression, 0, out)
+}
+
+/// Like `pack`, but sets `flags`, e.g. `FLAG_RAMDISK`, in the written header.
+pub fn pack_with_flags(
+    image: &[u8],
+    compression: Compression,
+    flags: u16,
+    out: &mut [u8]
+) -> Result<usize, Error> {
+    if out.len() < HEADER_LEN || image.len() > u32::MAX as usize {
+        return Err(Error::TooLarge);
+    }
+
+    let payload_len = {
+        let payload = &mut out[HEADER_LEN..];
+        match compression {
+            Compression::None => {
+                if payload.len() < image.len() {
+                    return Err(Error::TooLarge);
+                }
+                payload[..image.len()].copy_from_slice(image);
+                image.len()
+            }
+            Compression::Lz4 => lz4::compress(image, payload)?,
+        }
+    };
+
+    let header = Header {
+        compression,
+        flags,
+        payload_len: payload_len as u32,
+        image_len: image.len() as u32,
+    };
+    out[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+    Ok(HEADER_LEN + payload_len)
+}
+
+/// Unpacks the boot image in `data` into `dest`. Returns the number of bytes
+/// written to `dest`.
+///
+/// Data without a boot image header is treated as a plain binary and copied
+/// into `dest` as-is. Trailing bytes after a header's payload, such as a
+/// signature or XMODEM padding, are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the header is invalid, the payload is truncated or
+/// corrupt, or `dest` is too small to hold the unpacked image.
+pub fn unpack(data: &[u8], dest: &mut [u8]) -> Result<usize, Error> {
+    if !Header::is_present(data) {
+        if data.len() > dest.len() {
+            return Err(Error::TooLarge);
+        }
+        dest[..data.len()].copy_from_slice(data);
+        return Ok(data.len());
+    }
+
+    let header = Header::parse(data)?;
+    let image_len = header.image_len as usize;
+    let payload = data[HEADER_LEN..].get(..header.payload_len as usize)
+        .ok_or(Error::Truncated)?;
+    let dest = dest.get_mut(..image_len).ok_or(Error::TooLarge)?;
+
+    let written = match header.compression {
+        Compression::None => {
+            if payload.len() != image_len {
+                return Err(Error::LengthMismatch);
+            }
+            dest.copy_from_slice(payload);
+            image_len
+        }
+        Compression::Lz4 => lz4::decompress(payload, dest)?,
+    };
+
+    if written != image_len {
+        return Err(Error::LengthMismatch);
+    }
+
+    Ok(written)
+}
+
+/// Returns an iterator over the packed images stored back to back in `data`.
+///
+/// Iteration ends at the first byte that doesn't start a header, such as
+/// XMODEM padding. Each item is the complete packed image, suitable for
+/// `unpack`.
+pub fn images(data: &[u8]) -> Images<'_> {
+    Images { data }
+}
+
+/// An iterator over back-to-back packed images. See `images`.
+#[derive(Debug)]
+pub struct Images<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Images<'a> {
+    type Item = Result<&'a [u8], Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !Header::is_present(self.data) {
+            return None;
+        }
+
+        let result = Header::parse(self.data).and_then(|header| {
+            let len = header.packed_len();
+            if len > self.data.len() {
+                return Err(Error::Truncated);
+            }
+
+            let (image, rest) = self.data.split_at(len);
+            self.data = rest;
+            Ok(image)
+        });
+
+        // Don't try to resynchronize after a bad header.
+        if result.is_err() {
+            self.data = &[];
+        }
+
+        Some(result)
+    }
+}
@@ -0,0 +1,202 @@
+//! LZ4 block format compression and decompression.
+//!
+//! Neither direction allocates: both work entirely on caller-supplied slices
+//! so that the decompressor can run in the bootloader, which has no heap.
+
+use Error;
+
+/// Minimum length of a match.
+const MIN_MATCH: usize = 4;
+
+/// The last `LAST_LITERALS` bytes of a block are always literals.
+const LAST_LITERALS: usize = 5;
+
+/// A match can't start within the last `MF_LIMIT` bytes of a block.
+const MF_LIMIT: usize = 12;
+
+/// Largest offset a match may refer back to.
+const MAX_OFFSET: usize = 0xFFFF;
+
+/// log2 of the number of entries in the compressor's hash table.
+const HASH_LOG: u32 = 12;
+
+/// Returns the maximum number of bytes `len` input bytes compress to.
+pub fn max_compressed_len(len: usize) -> usize {
+    len + len / 255 + 16
+}
+
+/// Byte-at-a-time writer over an output slice.
+struct Output<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Output<'a> {
+    fn push(&mut self, byte: u8) -> Result<(), Error> {
+        *self.buf.get_mut(self.pos).ok_or(Error::TooLarge)? = byte;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn extend(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.pos + bytes.len();
+        self.buf.get_mut(self.pos..end).ok_or(Error::TooLarge)?.copy_from_slice(bytes);
+        self.pos = end;
+        Ok(())
+    }
+
+    /// Writes the continuation bytes of a literal or match length.
+    fn length(&mut self, mut len: usize) -> Result<(), Error> {
+        while len >= 255 {
+            self.push(255)?;
+            len -= 255;
+        }
+        self.push(len as u8)
+    }
+
+    /// Writes one sequence: `literals` followed by an optional match given as
+    /// `(offset, length)`.
+    fn sequence(&mut self, literals: &[u8], matched: Option<(usize, usize)>) -> Result<(), Error> {
+        let lit_len = literals.len();
+        let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+        let token = ((lit_len.min(15) as u8) << 4) | match_len.min(15) as u8;
+
+        self.push(token)?;
+        if lit_len >= 15 {
+            self.length(lit_len - 15)?;
+        }
+        self.extend(literals)?;
+
+        if let Some((offset, _)) = matched {
+            self.push(offset as u8)?;
+            self.push((offset >> 8) as u8)?;
+            if match_len >= 15 {
+                self.length(match_len - 15)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_u32(data: &[u8], i: usize) -> u32 {
+    u32::from(data[i])
+        | (u32::from(data[i + 1]) << 8)
+        | (u32::from(data[i + 2]) << 16)
+        | (u32::from(data[i + 3]) << 24)
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+/// Compresses `src` into `dst` as a single LZ4 block. Returns the number of
+/// bytes written to `dst`.
+///
+/// # Errors
+///
+/// Returns `TooLarge` if `dst` can't hold the compressed block. A `dst` of at
+/// least `max_compressed_len(src.len())` bytes never fails.
+pub fn compress(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    let mut out = Output { buf: dst, pos: 0 };
+    let mut table = [0u32; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut i = 0;
+
+    if src.len() > MF_LIMIT {
+        let match_limit = src.len() - MF_LIMIT;
+        let end_limit = src.len() - LAST_LITERALS;
+        while i < match_limit {
+            let sequence = read_u32(src, i);
+            let slot = &mut table[hash(sequence)];
+            let candidate = *slot as usize;
+            *slot = i as u32;
+
+            if candidate >= i || i - candidate > MAX_OFFSET || read_u32(src, candidate) != sequence {
+                i += 1;
+                continue;
+            }
+
+            let mut len = MIN_MATCH;
+            while i + len < end_limit && src[candidate + len] == src[i + len] {
+                len += 1;
+            }
+
+            out.sequence(&src[anchor..i], Some((i - candidate, len)))?;
+            i += len;
+            anchor = i;
+        }
+    }
+
+    out.sequence(&src[anchor..], None)?;
+    Ok(out.pos)
+}
+
+/// Reads the continuation bytes of a literal or match length from `src`
+/// starting at `*i`.
+fn read_length(src: &[u8], i: &mut usize) -> Result<usize, Error> {
+    let mut len = 0usize;
+    loop {
+        let byte = *src.get(*i).ok_or(Error::Corrupt)?;
+        *i += 1;
+        len = len.checked_add(byte as usize).ok_or(Error::Corrupt)?;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+/// Decompresses the LZ4 block `src` into `dst`. Returns the number of bytes
+/// written to `dst`.
+///
+/// # Errors
+///
+/// Returns `Corrupt` if `src` is not a well-formed block and `TooLarge` if the
+/// decompressed data doesn't fit in `dst`.
+pub fn decompress(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+    let (mut i, mut o) = (0, 0);
+    loop {
+        let token = *src.get(i).ok_or(Error::Corrupt)?;
+        i += 1;
+
+        let mut lit_len = (token >> 4) as usize;
+        if lit_len == 15 {
+            lit_len += read_length(src, &mut i)?;
+        }
+
+        let literals = src.get(i..i + lit_len).ok_or(Error::Corrupt)?;
+        dst.get_mut(o..o + lit_len).ok_or(Error::TooLarge)?.copy_from_slice(literals);
+        i += lit_len;
+        o += lit_len;
+
+        // The final sequence carries only literals.
+        if i == src.len() {
+            return Ok(o);
+        }
+
+        let offset = match src.get(i..i + 2) {
+            Some(bytes) => bytes[0] as usize | (bytes[1] as usize) << 8,
+            None => return Err(Error::Corrupt),
+        };
+        i += 2;
+        if offset == 0 || offset > o {
+            return Err(Error::Corrupt);
+        }
+
+        let mut match_len = (token & 0xF) as usize;
+        if match_len == 15 {
+            match_len += read_length(src, &mut i)?;
+        }
+        match_len += MIN_MATCH;
+
+        if o + match_len > dst.len() {
+            return Err(Error::TooLarge);
+        }
+
+        // Matches may overlap their own output, so copy byte by byte.
+        for _ in 0..match_len {
+            dst[o] = dst[o - offset];
+            o += 1;
+        }
+    }
+}
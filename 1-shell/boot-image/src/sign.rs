@@ -0,0 +1,92 @@
+//! Ed25519 signing and verification of boot images.
+//!
+//! Keys are handled as raw bytes: a 32-byte secret seed for signing and the
+//! 32-byte public key derived from it for verification.
+
+extern crate ed25519_compact;
+
+use self::ed25519_compact::{KeyPair, PublicKey, Seed, Signature};
+use {pack_with_flags, unpack, Compression, Error, Header, FLAG_SIGNED, HEADER_LEN, SIGNATURE_LEN};
+
+/// Size of a secret signing seed, in bytes.
+pub const SEED_LEN: usize = 32;
+
+/// Size of a public verification key, in bytes.
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+fn key_pair(seed: &[u8; SEED_LEN]) -> Result<KeyPair, Error> {
+    // An all-zero seed is almost certainly an uninitialized key file.
+    if seed.iter().all(|&b| b == 0) {
+        return Err(Error::BadKey);
+    }
+
+    Ok(KeyPair::from_seed(Seed::new(*seed)))
+}
+
+/// Returns the public key corresponding to the secret `seed`.
+///
+/// # Errors
+///
+/// Returns `BadKey` if `seed` is all zeroes.
+pub fn public_key(seed: &[u8; SEED_LEN]) -> Result<[u8; PUBLIC_KEY_LEN], Error> {
+    Ok(*key_pair(seed)?.pk)
+}
+
+/// Packs `image` like `boot_image::pack_with_flags` and appends an Ed25519
+/// signature made with `seed`. Returns the number of bytes written to `out`, which must
+/// have room for `SIGNATURE_LEN` bytes past the packed image.
+///
+/// # Errors
+///
+/// Returns `BadKey` if `seed` is all zeroes and `TooLarge` if `out` is too
+/// small.
+pub fn pack_signed(
+    image: &[u8],
+    compression: Compression,
+    flags: u16,
+    seed: &[u8; SEED_LEN],
+    out: &mut [u8]
+) -> Result<usize, Error> {
+    let key_pair = key_pair(seed)?;
+    let len = pack_with_flags(image, compression, flags | FLAG_SIGNED, out)?;
+    let signature = key_pair.sk.sign(&out[..len], None);
+    out.get_mut(len..len + SIGNATURE_LEN)
+        .ok_or(Error::TooLarge)?
+        .copy_from_slice(&signature[..]);
+    Ok(len + SIGNATURE_LEN)
+}
+
+/// Verifies the signature on the boot image in `data` against `public_key`
+/// and, if it is valid, unpacks the image into `dest`. Returns the number of
+/// bytes written to `dest`.
+///
+/// Unlike `boot_image::unpack`, plain binaries and unsigned images are
+/// rejected.
+///
+/// # Errors
+///
+/// Returns `Unsigned` if `data` is not a signed image, `BadSignature` if the
+/// signature doesn't verify, and otherwise any error `unpack` returns.
+pub fn unpack_verified(
+    data: &[u8],
+    dest: &mut [u8],
+    public_key: &[u8; PUBLIC_KEY_LEN]
+) -> Result<usize, Error> {
+    if !Header::is_present(data) {
+        return Err(Error::Unsigned);
+    }
+
+    let header = Header::parse(data)?;
+    if !header.is_signed() {
+        return Err(Error::Unsigned);
+    }
+
+    let signed_len = HEADER_LEN + header.payload_len as usize;
+    let signature = data.get(signed_len..signed_len + SIGNATURE_LEN).ok_or(Error::Truncated)?;
+    let signature = Signature::from_slice(signature).map_err(|_| Error::BadSignature)?;
+    PublicKey::new(*public_key)
+        .verify(&data[..signed_len], &signature)
+        .map_err(|_| Error::BadSignature)?;
+
+    unpack(data, dest)
+}
@@ -0,0 +1,262 @@
+extern crate std;
+
+use self::std::vec::Vec;
+use self::std::vec;
+
+use {lz4, pack, pack_with_flags, unpack, images, max_packed_len};
+use {Compression, Error, Header, FLAG_RAMDISK};
+
+/// Deterministic pseudo-random bytes with a tunable amount of repetition.
+fn sample(len: usize, alphabet: u32) -> Vec<u8> {
+    let mut state = 0x2545F491u32;
+    (0..len).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state % alphabet) as u8
+    }).collect()
+}
+
+fn roundtrip(data: &[u8]) {
+    let mut compressed = vec![0u8; lz4::max_compressed_len(data.len())];
+    let n = lz4::compress(data, &mut compressed).expect("compress");
+
+    let mut decompressed = vec![0u8; data.len()];
+    let m = lz4::decompress(&compressed[..n], &mut decompressed).expect("decompress");
+    assert_eq!(m, data.len());
+    assert_eq!(&decompressed[..], data);
+}
+
+#[test]
+fn lz4_roundtrip() {
+    roundtrip(&[]);
+    roundtrip(b"a");
+    roundtrip(b"hello, world");
+    roundtrip(&[0u8; 10000]);
+    roundtrip(&sample(70000, 4));
+    roundtrip(&sample(70000, 256));
+    roundtrip(&sample(300, 2));
+}
+
+#[test]
+fn lz4_compresses_repetition() {
+    let data = [0xAAu8; 4096];
+    let mut compressed = [0u8; 4096];
+    let n = lz4::compress(&data, &mut compressed).expect("compress");
+    assert!(n < 64, "compressed to {} bytes", n);
+}
+
+#[test]
+fn lz4_reference_block() {
+    // "abcabcabcabcabcabc" with one 15-byte overlapping match.
+    let block = [0x3B, b'a', b'b', b'c', 0x03, 0x00, 0x00];
+    let mut out = [0u8; 18];
+    assert_eq!(lz4::decompress(&block, &mut out), Ok(18));
+    assert_eq!(&out, b"abcabcabcabcabcabc");
+}
+
+#[test]
+fn lz4_rejects_bad_streams() {
+    let mut out = [0u8; 64];
+    assert_eq!(lz4::decompress(&[], &mut out), Err(Error::Corrupt));
+    // Literal run longer than the input.
+    assert_eq!(lz4::decompress(&[0x50, b'a'], &mut out), Err(Error::Corrupt));
+    // Match offset pointing before the start of the output.
+    assert_eq!(lz4::decompress(&[0x10, b'a', 0x02, 0x00], &mut out), Err(Error::Corrupt));
+    // Zero offset.
+    assert_eq!(lz4::decompress(&[0x10, b'a', 0x00, 0x00], &mut out), Err(Error::Corrupt));
+    // Output too small.
+    let mut small = [0u8; 4];
+    assert_eq!(lz4::decompress(&[0x3B, b'a', b'b', b'c', 0x03, 0x00, 0x00], &mut small),
+               Err(Error::TooLarge));
+}
+
+#[test]
+fn header_roundtrip() {
+    let header = Header {
+        compression: Compression::Lz4,
+        flags: 0x1234,
+        payload_len: 0xDEADBEEF,
+        image_len: 42,
+    };
+
+    let bytes = header.to_bytes();
+    assert!(Header::is_present(&bytes));
+    assert_eq!(Header::parse(&bytes), Ok(header));
+}
+
+#[test]
+fn header_rejects_garbage() {
+    let mut bytes = Header {
+        compression: Compression::None,
+        flags: 0,
+        payload_len: 0,
+        image_len: 0,
+    }.to_bytes();
+
+    assert_eq!(Header::parse(&bytes[..8]), Err(Error::BadHeader));
+
+    bytes[5] = 9;
+    assert_eq!(Header::parse(&bytes), Err(Error::UnsupportedCompression(9)));
+
+    bytes[4] = 0;
+    assert_eq!(Header::parse(&bytes), Err(Error::BadHeader));
+}
+
+#[test]
+fn pack_unpack() {
+    let image = sample(5000, 16);
+    for &compression in &[Compression::None, Compression::Lz4] {
+        let mut packed = vec![0u8; max_packed_len(image.len(), compression)];
+        let n = pack(&image, compression, &mut packed).expect("pack");
+
+        // Simulate XMODEM padding to a multiple of 128 bytes.
+        packed.truncate(n);
+        while !packed.len().is_multiple_of(128) {
+            packed.push(0);
+        }
+
+        let mut dest = vec![0u8; image.len() + 100];
+        assert_eq!(unpack(&packed, &mut dest), Ok(image.len()));
+        assert_eq!(&dest[..image.len()], &image[..]);
+    }
+}
+
+#[test]
+fn unpack_plain_binary() {
+    let image = [0x1Fu8, 0x20, 0x03, 0xD5, 0xC0, 0x03, 0x5F, 0xD6];
+    let mut dest = [0u8; 16];
+    assert_eq!(unpack(&image, &mut dest), Ok(image.len()));
+    assert_eq!(&dest[..image.len()], &image);
+
+    let mut small = [0u8; 4];
+    assert_eq!(unpack(&image, &mut small), Err(Error::TooLarge));
+}
+
+#[test]
+fn unpack_errors() {
+    let image = sample(1000, 8);
+    let mut packed = vec![0u8; max_packed_len(image.len(), Compression::Lz4)];
+    let n = pack(&image, Compression::Lz4, &mut packed).expect("pack");
+
+    let mut dest = vec![0u8; image.len()];
+    assert_eq!(unpack(&packed[..n - 1], &mut dest), Err(Error::Truncated));
+
+    let mut small = vec![0u8; image.len() - 1];
+    assert_eq!(unpack(&packed[..n], &mut small), Err(Error::TooLarge));
+
+    let mut lying = packed[..n].to_vec();
+    lying[12] = lying[12].wrapping_sub(1);
+    assert!(unpack(&lying, &mut dest).is_err());
+}
+
+#[test]
+fn iterate_images() {
+    let kernel = sample(3000, 16);
+    let ramdisk = sample(1000, 200);
+
+    let mut bundle = vec![0u8; max_packed_len(kernel.len(), Compression::Lz4)];
+    let n = pack(&kernel, Compression::Lz4, &mut bundle).expect("pack kernel");
+    bundle.truncate(n);
+
+    let mut packed = vec![0u8; max_packed_len(ramdisk.len(), Compression::None)];
+    let m = pack_with_flags(&ramdisk, Compression::None, FLAG_RAMDISK, &mut packed)
+        .expect("pack ramdisk");
+    bundle.extend_from_slice(&packed[..m]);
+    bundle.extend_from_slice(&[0; 128]);
+
+    let found: Vec<_> = images(&bundle).collect();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].map(|i| i.len()), Ok(n));
+    assert_eq!(found[1].map(|i| i.len()), Ok(m));
+    assert!(!Header::parse(found[0].unwrap()).unwrap().is_ramdisk());
+    assert!(Header::parse(found[1].unwrap()).unwrap().is_ramdisk());
+
+    let mut dest = vec![0u8; ramdisk.len()];
+    assert_eq!(unpack(found[1].unwrap(), &mut dest), Ok(ramdisk.len()));
+    assert_eq!(dest, ramdisk);
+
+    assert_eq!(images(&bundle[..n + m - 1]).nth(1), Some(Err(Error::Truncated)));
+    assert_eq!(images(&ramdisk).next(), None);
+}
+
+#[cfg(feature = "signatures")]
+mod signatures {
+    use super::*;
+    use sign::{pack_signed, public_key, unpack_verified};
+    use {SIGNATURE_LEN, HEADER_LEN};
+
+    // Test 1 from RFC 8032, section 7.1.
+    const SEED: [u8; 32] = [
+        0x9d, 0x61, 0xb1, 0x9d, 0xef, 0xfd, 0x5a, 0x60, 0xba, 0x84, 0x4a, 0xf4, 0x92, 0xec, 0x2c, 0xc4,
+        0x44, 0x49, 0xc5, 0x69, 0x7b, 0x32, 0x69, 0x19, 0x70, 0x3b, 0xac, 0x03, 0x1c, 0xae, 0x7f, 0x60,
+    ];
+
+    const PUBLIC_KEY: [u8; 32] = [
+        0xd7, 0x5a, 0x98, 0x01, 0x82, 0xb1, 0x0a, 0xb7, 0xd5, 0x4b, 0xfe, 0xd3, 0xc9, 0x64, 0x07, 0x3a,
+        0x0e, 0xe1, 0x72, 0xf3, 0xda, 0xa6, 0x23, 0x25, 0xaf, 0x02, 0x1a, 0x68, 0xf7, 0x07, 0x51, 0x1a,
+    ];
+
+    fn signed(image: &[u8], compression: Compression) -> Vec<u8> {
+        let mut packed = vec![0u8; max_packed_len(image.len(), compression) + SIGNATURE_LEN];
+        let n = pack_signed(image, compression, 0, &SEED, &mut packed).expect("pack signed");
+        packed.truncate(n);
+        packed
+    }
+
+    #[test]
+    fn derives_public_key() {
+        assert_eq!(public_key(&SEED), Ok(PUBLIC_KEY));
+        assert_eq!(public_key(&[0; 32]), Err(Error::BadKey));
+    }
+
+    #[test]
+    fn signed_roundtrip() {
+        let image = sample(3000, 32);
+        for &compression in &[Compression::None, Compression::Lz4] {
+            let mut packed = signed(&image, compression);
+            packed.extend_from_slice(&[0; 100]);
+
+            let mut dest = vec![0u8; image.len()];
+            assert_eq!(unpack_verified(&packed, &mut dest, &PUBLIC_KEY), Ok(image.len()));
+            assert_eq!(dest, image);
+
+            // Signed images still load without verification.
+            assert_eq!(unpack(&packed, &mut dest), Ok(image.len()));
+        }
+    }
+
+    #[test]
+    fn rejects_unsigned() {
+        let image = sample(500, 32);
+        let mut dest = vec![0u8; image.len()];
+        assert_eq!(unpack_verified(&image, &mut dest, &PUBLIC_KEY), Err(Error::Unsigned));
+
+        let mut packed = vec![0u8; max_packed_len(image.len(), Compression::Lz4)];
+        let n = pack(&image, Compression::Lz4, &mut packed).expect("pack");
+        assert_eq!(unpack_verified(&packed[..n], &mut dest, &PUBLIC_KEY), Err(Error::Unsigned));
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let image = sample(500, 32);
+        let packed = signed(&image, Compression::None);
+        let mut dest = vec![0u8; image.len()];
+
+        let mut tampered = packed.clone();
+        tampered[HEADER_LEN + 7] ^= 1;
+        assert_eq!(unpack_verified(&tampered, &mut dest, &PUBLIC_KEY), Err(Error::BadSignature));
+
+        let mut tampered = packed.clone();
+        tampered[5] = Compression::None as u8;
+        tampered[6] = 0xFF;
+        assert!(unpack_verified(&tampered, &mut dest, &PUBLIC_KEY).is_err());
+
+        let mut other_key = PUBLIC_KEY;
+        other_key[0] ^= 0x80;
+        assert!(unpack_verified(&packed, &mut dest, &other_key).is_err());
+
+        let truncated = &packed[..packed.len() - 1];
+        assert_eq!(unpack_verified(truncated, &mut dest, &PUBLIC_KEY), Err(Error::Truncated));
+    }
+}
@@ -3,18 +3,20 @@ extern crate structopt;
 #[macro_use]
 extern crate structopt_derive;
 extern crate xmodem;
+extern crate boot_image;
 
-use std::{time::Instant, io::Write};
+use std::{fs, time::Instant, io::Write};
 use std::path::PathBuf;
 use std::time::Duration;
 
 use structopt::StructOpt;
 use serial::{core::{BaudRate, CharSize, FlowControl, SerialDevice, SerialPortSettings, StopBits}, SerialPort};
 use xmodem::{Progress, Xmodem};
+use boot_image::Compression;
 
 mod parsers;
 
-use parsers::{parse_baud_rate, parse_flow_control, parse_stop_bits, parse_width};
+use parsers::{parse_baud_rate, parse_compression, parse_flow_control, parse_stop_bits, parse_width};
 
 #[derive(StructOpt, Debug)]
 #[structopt(about = "Write to TTY using the XMODEM protocol by default.")]
@@ -48,6 +50,18 @@ struct Opt {
 
     #[structopt(short = "r", long = "raw", help = "Disable XMODEM")]
     raw: bool,
+
+    #[structopt(short = "c", long = "compress", parse(try_from_str = "parse_compression"),
+                help = "Pack the input as a boot image compressed with 'none' or 'lz4'")]
+    compression: Option<Compression>,
+
+    #[structopt(short = "k", long = "sign-key", parse(from_os_str),
+                help = "Sign the boot image with the 32-byte Ed25519 seed in this file")]
+    sign_key: Option<PathBuf>,
+
+    #[structopt(long = "initrd", parse(from_os_str),
+                help = "Send this ramdisk image after the kernel in the same session")]
+    initrd: Option<PathBuf>,
 }
 
 fn progress_fn(_progress: Progress) {
@@ -72,6 +86,47 @@ fn progress_fn(_progress: Progress) {
     }
 }
 
+/// Reads a raw 32-byte Ed25519 seed from the key file at `path`.
+fn read_seed(path: &PathBuf) -> [u8; boot_image::sign::SEED_LEN] {
+    let bytes = fs::read(path).expect("read key file fail");
+    let mut seed = [0; boot_image::sign::SEED_LEN];
+    if bytes.len() != seed.len() {
+        panic!("key file must contain exactly {} bytes", seed.len());
+    }
+    seed.copy_from_slice(&bytes);
+    seed
+}
+
+/// Formats `bytes` as lowercase hex, e.g. for `BOOT_PUBLIC_KEY`.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Packs `image` as a boot image with header flags `flags`, compressing it
+/// with `compression` and signing it with `seed` if there is one.
+fn pack(image: &[u8], flags: u16, compression: Compression,
+        seed: Option<&[u8; boot_image::sign::SEED_LEN]>) -> Vec<u8> {
+    let mut packed = vec![0; boot_image::max_packed_len(image.len(), compression)];
+
+    let n = if let Some(seed) = seed {
+        let public_key = boot_image::sign::public_key(seed).expect("invalid signing key");
+        packed.resize(packed.len() + boot_image::SIGNATURE_LEN, 0);
+        let n = boot_image::sign::pack_signed(image, compression, flags, seed, &mut packed)
+            .expect("image sign fail");
+        println!("signed {} bytes into {} bytes with public key {}",
+                 image.len(), n, to_hex(&public_key));
+        n
+    } else {
+        let n = boot_image::pack_with_flags(image, compression, flags, &mut packed)
+            .expect("image pack fail");
+        println!("packed {} bytes into {} bytes", image.len(), n);
+        n
+    };
+
+    packed.truncate(n);
+    packed
+}
+
 fn main() {
     use std::fs::File;
     use std::io::{self, BufReader};
@@ -90,38 +145,29 @@ fn main() {
     });
     serial::SerialPort::set_timeout(&mut serial,Duration::new(opt.timeout,0)).expect("set time fail");
 
-    let mut len: usize = 0;
-    match (opt.raw, opt.input) {
-        (true, None) => {
-            let input = io::stdin();
-            let mut br = BufReader::new(input);
-            let mut v = vec![];
-            io::copy(&mut br, &mut v).expect("copy fail");
-            serial.write(&v).expect("serial write fail");
-            len = v.len();
-        },
-        (true, Some(file)) => {
-            let input = File::open(file.as_path()).expect("open file fail");
-            let mut br = BufReader::new(input);
-            let mut v = vec![];
-            io::copy(&mut br, &mut v).expect("copy fail");
-            serial.write(&v).expect("serial write fail");
-            len = v.len();
-        },
-        (false, None) => {
-            let input = io::stdin();
-            let mut br = BufReader::new(input);
-            let mut v = vec![];
-            io::copy(&mut br, &mut v).expect("copy fail");
-            len = Xmodem::transmit_with_progress(&v[..], serial, progress_fn).expect("Xmodem transmit fail");
-        }
-        (false, Some(file)) => {
-            let input = File::open(file.as_path()).expect("open file fail");
-            let mut br = BufReader::new(input);
-            let mut v = vec![];
-            io::copy(&mut br, &mut v).expect("copy fail");
-            len = Xmodem::transmit_with_progress(&v[..], serial, progress_fn).expect("Xmodem transmit fail");
+    let mut input: Box<dyn io::Read> = match opt.input {
+        Some(ref file) => Box::new(File::open(file.as_path()).expect("open file fail")),
+        None => Box::new(io::stdin()),
+    };
+    let mut v = vec![];
+    io::copy(&mut BufReader::new(&mut input), &mut v).expect("copy fail");
+
+    if opt.compression.is_some() || opt.sign_key.is_some() || opt.initrd.is_some() {
+        let compression = opt.compression.unwrap_or(Compression::None);
+        let seed = opt.sign_key.as_ref().map(read_seed);
+        let mut bundle = pack(&v, 0, compression, seed.as_ref());
+        if let Some(ref initrd) = opt.initrd {
+            let ramdisk = fs::read(initrd).expect("read initrd fail");
+            bundle.extend(pack(&ramdisk, boot_image::FLAG_RAMDISK, compression, seed.as_ref()));
         }
+        v = bundle;
     }
+
+    let len = if opt.raw {
+        serial.write_all(&v).expect("serial write fail");
+        v.len()
+    } else {
+        Xmodem::transmit_with_progress(&v[..], serial, progress_fn).expect("Xmodem transmit fail")
+    };
     println!("wrote {len} bytes to {:?}" ,opt.tty_path);
 }
@@ -1,4 +1,5 @@
 use serial::core::{CharSize, BaudRate, StopBits, FlowControl};
+use boot_image::Compression;
 
 pub fn parse_width(s: &str) -> Result<CharSize, &str> {
     match s {
@@ -30,3 +31,11 @@ pub fn parse_flow_control(s: &str) -> Result<FlowControl, &str> {
 pub fn parse_baud_rate(s: &str) -> Result<BaudRate, ::std::num::ParseIntError> {
     Ok(BaudRate::from_speed(s.parse()?))
 }
+
+pub fn parse_compression(s: &str) -> Result<Compression, &str> {
+    match s {
+        "none" => Ok(Compression::None),
+        "lz4" => Ok(Compression::Lz4),
+        _ => Err("value must be 'none' or 'lz4'")
+    }
+}
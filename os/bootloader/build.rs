@@ -1,4 +1,38 @@
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
 pub fn main() {
     println!("cargo:rerun-if-changed=ext/layout.ld");
     println!("cargo:rerun-if-changed=ext/init.S");
+    println!("cargo:rerun-if-env-changed=BOOT_INITRD_ADDR");
+    println!("cargo:rerun-if-env-changed=BOOT_BAUD_RATE");
+
+    if env::var_os("CARGO_FEATURE_SECURE_BOOT").is_some() {
+        write_public_key();
+    }
+}
+
+/// Writes the Ed25519 public key given in hex by `BOOT_PUBLIC_KEY` to
+/// `$OUT_DIR/public_key.rs` as a `PUBLIC_KEY` constant.
+fn write_public_key() {
+    println!("cargo:rerun-if-env-changed=BOOT_PUBLIC_KEY");
+
+    let hex = env::var("BOOT_PUBLIC_KEY").expect("secure-boot requires BOOT_PUBLIC_KEY");
+    let hex = hex.trim();
+    if hex.len() != 64 {
+        panic!("BOOT_PUBLIC_KEY must be 64 hex digits, found {}", hex.len());
+    }
+
+    let bytes: Vec<String> = (0..32)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+             .expect("BOOT_PUBLIC_KEY must be hexadecimal"))
+        .map(|byte| format!("{:#04x}", byte))
+        .collect();
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("public_key.rs");
+    let mut file = File::create(path).expect("create public_key.rs");
+    writeln!(file, "pub const PUBLIC_KEY: [u8; 32] = [{}];", bytes.join(", "))
+        .expect("write public_key.rs");
 }
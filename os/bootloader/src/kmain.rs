@@ -8,8 +8,12 @@ extern crate std;
 
 extern crate pi;
 extern crate xmodem;
+extern crate boot_image;
 
-use pi::uart::MiniUart;
+use pi::uart::{MiniUart, MiniUartConfig};
+use pi::boot::{BootInfo, LoadSource};
+use pi::atags::{self, ATAG_BASE};
+use boot_image::{Error, Header};
 
 pub mod mutex;
 pub mod console;
@@ -21,42 +25,177 @@ use std::io::ErrorKind;
 
 use std::io::Cursor;
 
-/// Start address of the binary to load and of the bootloader.
+/// Start address of the binary to load.
 const BINARY_START_ADDR: usize = 0x80000;
-const BOOTLOADER_START_ADDR: usize = 0x4000000;
 
 /// Pointer to where the loaded binary expects to be laoded.
 const BINARY_START: *mut u8 = BINARY_START_ADDR as *mut u8;
 
-/// Free space between the bootloader and the loaded binary's start address.
-const MAX_BINARY_SIZE: usize = BOOTLOADER_START_ADDR - BINARY_START_ADDR;
+/// Bytes reserved for the bootloader's stack, which `init.S` places directly
+/// below the relocated bootloader.
+const STACK_SIZE: usize = 0x100000;
 
-/// Branches to the address `addr` unconditionally.
-fn jump_to(addr: *mut u8) -> ! {
+extern "C" {
+    /// Start of the bootloader. `init.S` relocates the bootloader to the top
+    /// of RAM, so this is only known at runtime.
+    static _start: u8;
+}
+
+/// Alignment of the ramdisk's load address.
+const INITRD_ALIGN: usize = 0x1000;
+
+/// Free space between the loaded binary's start address and the bootloader's
+/// stack.
+fn max_binary_size() -> usize {
+    let bootloader_start = unsafe { &_start as *const u8 as usize };
+    bootloader_start - STACK_SIZE - BINARY_START_ADDR
+}
+
+/// The key images must be signed with when booting securely.
+#[cfg(feature = "secure-boot")]
+mod key {
+    include!(concat!(env!("OUT_DIR"), "/public_key.rs"));
+}
+
+/// Unpacks the image received in `data` into `dest`.
+#[cfg(not(feature = "secure-boot"))]
+fn load(data: &[u8], dest: &mut [u8]) -> Result<usize, Error> {
+    boot_image::unpack(data, dest)
+}
+
+/// Unpacks the image received in `data` into `dest`. Plain binaries, unsigned
+/// images and images whose signature doesn't match `key::PUBLIC_KEY` are
+/// rejected before anything is written to `dest`.
+#[cfg(feature = "secure-boot")]
+fn load(data: &[u8], dest: &mut [u8]) -> Result<usize, Error> {
+    boot_image::sign::unpack_verified(data, dest, &key::PUBLIC_KEY)
+}
+
+/// Returns the ramdisk load address set with `BOOT_INITRD_ADDR` (in hex) at
+/// build time, if any.
+fn initrd_addr() -> Option<usize> {
+    let addr = option_env!("BOOT_INITRD_ADDR")?;
+    let addr = addr.trim_start_matches("0x");
+    usize::from_str_radix(addr, 16).ok()
+}
+
+/// Why a received upload couldn't be loaded.
+#[derive(Debug)]
+enum LoadError {
+    /// An image in the upload is malformed, doesn't fit or isn't trusted.
+    Image(Error),
+    /// The upload holds a ramdisk, but the firmware passed a device tree
+    /// rather than ATAGs, so there's no `INITRD2` ATAG to describe it with.
+    NoAtags,
+}
+
+impl From<Error> for LoadError {
+    fn from(error: Error) -> LoadError {
+        LoadError::Image(error)
+    }
+}
+
+/// Returns the UART baud rate set with `BOOT_BAUD_RATE` at build time, or
+/// 115200 if it isn't set.
+fn baud_rate() -> u32 {
+    option_env!("BOOT_BAUD_RATE").and_then(|baud| baud.parse().ok()).unwrap_or(115200)
+}
+
+/// Unpacks the kernel received in `data` to the start of `dest`. If `data`
+/// also holds a ramdisk image, it is unpacked to `initrd_addr()`, or as high
+/// in `dest` as possible if that isn't set, and described by an `INITRD2` ATAG
+/// appended to the ATAGs at `tags`.
+///
+/// Returns the size of the kernel.
+fn load_all(data: &[u8], dest: &mut [u8], tags: usize) -> Result<usize, LoadError> {
+    // Plain binaries can't be followed by another image.
+    if !Header::is_present(data) {
+        return Ok(load(data, dest)?);
+    }
+
+    let (mut kernel, mut ramdisk) = (None, None);
+    for image in boot_image::images(data) {
+        let image = image?;
+        let slot = if Header::parse(image)?.is_ramdisk() { &mut ramdisk } else { &mut kernel };
+        if slot.replace(image).is_some() {
+            return Err(Error::BadHeader.into());
+        }
+    }
+
+    let kernel_size = load(kernel.ok_or(Error::BadHeader)?, dest)?;
+    if let Some(ramdisk) = ramdisk {
+        let ramdisk_size = Header::parse(ramdisk)?.image_len as usize;
+        let offset = match initrd_addr() {
+            Some(addr) => addr.checked_sub(BINARY_START_ADDR).ok_or(Error::TooLarge)?,
+            None => dest.len().checked_sub(ramdisk_size).ok_or(Error::TooLarge)? & !(INITRD_ALIGN - 1),
+        };
+        if offset < kernel_size || offset > dest.len() {
+            return Err(Error::TooLarge.into());
+        }
+
+        let size = load(ramdisk, &mut dest[offset..])?;
+        let start = (BINARY_START_ADDR + offset) as u32;
+        if !unsafe { atags::append_initrd2(tags, start, size as u32) } {
+            return Err(LoadError::NoAtags);
+        }
+    }
+
+    Ok(kernel_size)
+}
+
+/// Returns this bootloader's version as `major << 16 | minor << 8 | patch`.
+fn loader_version() -> u32 {
+    let major: u32 = env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0);
+    let minor: u32 = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0);
+    let patch: u32 = env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0);
+    (major << 16) | ((minor & 0xFF) << 8) | (patch & 0xFF)
+}
+
+/// Branches to the address `addr` unconditionally, handing off `tags` in `x0`
+/// and the address of `info` in `x1`.
+fn jump_to(addr: *mut u8, tags: usize, info: &BootInfo) -> ! {
     unsafe {
         // asm!("br $0" : : "r"(addr as usize));
         // loop {
         //     asm!("nop" :::: "volatile")
         // }
-        asm!("br {0}", in(reg) addr);
+        asm!("br {0}", in(reg) addr, in("x0") tags, in("x1") info as *const BootInfo);
         loop { asm!("nop", options(nomem, nostack, preserves_flags)) }
     }
     
 }
 
+/// Entry point, called by `init.S` with the firmware's `x0` in `tags`.
 #[no_mangle]
-pub extern "C" fn kmain() {
+pub extern "C" fn kmain(tags: usize) {
+    let tags = if tags == 0 { ATAG_BASE } else { tags };
     // FIXME: Implement the bootloader.
     // ALLOCATOR.initialize();
-    let mut uart = MiniUart::new();
-    uart.set_read_timeout(750);
+    let mut uart = MiniUart::with_config(MiniUartConfig {
+        baud_rate: baud_rate(),
+        read_timeout: Some(750),
+        ..MiniUartConfig::default()
+    });
 
     loop {
-        let dest = unsafe { std::slice::from_raw_parts_mut(BINARY_START, MAX_BINARY_SIZE) };
-        match xmodem::Xmodem::receive(&mut uart, Cursor::new(dest)) {
-            Ok(_) => {
-                // Succeed
-                jump_to(BINARY_START)
+        let free = unsafe { std::slice::from_raw_parts_mut(BINARY_START, max_binary_size()) };
+        match xmodem::Xmodem::receive(&mut uart, Cursor::new(&mut free[..])) {
+            Ok(received) => {
+                // Move the received data to the top of the free space so it
+                // can be unpacked down into `BINARY_START`. Plain binaries are
+                // copied back as-is; packed images are decompressed according
+                // to their header.
+                let data_start = free.len() - received;
+                free.copy_within(..received, data_start);
+                let (dest, data) = free.split_at_mut(data_start);
+                match load_all(data, dest, tags) {
+                    Ok(size) => {
+                        // Lives on this stack frame, which is never popped.
+                        let info = BootInfo::new(size as u64, LoadSource::Uart, loader_version());
+                        jump_to(BINARY_START, tags, &info)
+                    }
+                    Err(_) => continue,
+                }
             }
             Err(err) => match err.kind() {
                 ErrorKind::TimedOut => continue,
@@ -7,9 +7,10 @@ mod imp;
 mod tests;
 
 use mutex::Mutex;
-use pi::atags::Atags;
+use pi::atags::{Atags, ATAG_BASE};
+use pi::fdt::Fdt;
 use core::alloc::{GlobalAlloc, AllocError, Layout};
-use std::cmp::max;
+use std::cmp::{max, min};
 
 use crate::console::kprintln;
 
@@ -97,22 +98,65 @@ extern "C" {
 /// Returns the (start address, end address) of the available memory on this
 /// system if it can be determined. If it cannot, `None` is returned.
 ///
+/// The memory is read from the device tree the kernel was handed, if it was
+/// handed one, and from the ATAGs otherwise. The heap is kept clear of the
+/// device tree, the regions it reserves and the ramdisk the bootloader loaded,
+/// if any.
+///
 /// This function is expected to return `Some` under all normal cirumstances.
 fn memory_map() -> Option<(usize, usize)> {
     let binary_end = unsafe { (&_end as *const u8) as usize };
-    for tag in Atags::get() {
-        kprintln!("{:#?}", tag);
-        if let Some(mem) = tag.mem() {
-            let mut start = mem.start as usize;
-            let end = (mem.start + mem.size) as usize;
-
-            if binary_end < end {
-                start = max(start, binary_end);
+    // Firmware started without a device tree leaves the ATAGs at
+    // `ATAG_BASE` and may hand off `0`.
+    let tags = match crate::HANDOFF.lock().map_or(0, |handoff| handoff.tags) {
+        0 => ATAG_BASE,
+        tags => tags,
+    };
+    let (start, end) = match unsafe { Fdt::from_addr(tags) } {
+        Ok(fdt) => {
+            let memory = fdt.memory()?.next()?;
+            let end = (memory.address + memory.size) as usize;
+            let mut heap = above(binary_end, memory.address as usize, end);
+            heap = exclude(heap, tags, fdt.total_size());
+            for region in fdt.reservations().chain(fdt.initrd()) {
+                heap = exclude(heap, region.address as usize, region.size as usize);
             }
-            kprintln!("heap memory start:{}, end: {}", start, end);
-
-            return Some((start, end));
+            heap
         }
+        Err(_) => {
+            let mem = unsafe { Atags::at(tags) }?.filter_map(|tag| tag.mem()).next()?;
+            let heap = above(binary_end, mem.start as usize, (mem.start + mem.size) as usize);
+            match unsafe { Atags::at(tags) }?.filter_map(|tag| tag.initrd2()).next() {
+                Some(initrd) => exclude(heap, initrd.start as usize, initrd.size as usize),
+                None => heap,
+            }
+        }
+    };
+    kprintln!("heap memory start:{}, end: {}", start, end);
+
+    Some((start, end))
+}
+
+/// Returns the memory from `start` to `end`, less the part holding the kernel
+/// binary, which ends at `binary_end`.
+fn above(binary_end: usize, start: usize, end: usize) -> (usize, usize) {
+    if binary_end < end {
+        (max(start, binary_end), end)
+    } else {
+        (start, end)
+    }
+}
+
+/// Shrinks `heap` so it doesn't overlap the `size` bytes at `address`. A
+/// region starting inside the heap cuts it short; one starting below it moves
+/// its start past the region's end.
+fn exclude(heap: (usize, usize), address: usize, size: usize) -> (usize, usize) {
+    let (start, end) = heap;
+    if size == 0 {
+        heap
+    } else if address >= start {
+        (start, min(end, address))
+    } else {
+        (max(start, address.saturating_add(size)), end)
     }
-    return None;
 }
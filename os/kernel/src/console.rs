@@ -1,70 +1,250 @@
 use std::io;
 use core::fmt;
 
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+
+use pi::framebuffer::{self, Framebuffer, Surface, TextConsole};
+use pi::interrupt::{Controller, Interrupt};
+use pi::mailbox;
+use pi::pl011;
 use pi::uart::MiniUart;
 
+use irq;
 use mutex::Mutex;
+use ring_buffer::RingBuffer;
+
+/// A UART the console can run on.
+pub trait Serial: io::Read + io::Write + fmt::Write + Sized {
+    /// The interrupt raised while the UART has received data.
+    const RX_INTERRUPT: Interrupt;
+
+    /// Initializes the UART with its default configuration.
+    fn new() -> Self;
+
+    /// Returns a handle to the UART without reinitializing it.
+    ///
+    /// # Safety
+    ///
+    /// The UART must already have been initialized with `new()`, and the
+    /// caller must not race with another handle's use of its registers.
+    unsafe fn from_initialized() -> Self;
+
+    /// Returns `true` if a byte is ready to be read.
+    fn has_byte(&self) -> bool;
+
+    /// Reads a byte, blocking until one is ready.
+    fn read_byte(&mut self) -> u8;
+
+    /// Writes a byte, blocking until there is space for it.
+    fn write_byte(&mut self, byte: u8);
+
+    /// Enables or disables `RX_INTERRUPT`.
+    fn set_rx_interrupt(&mut self, enabled: bool);
+
+    /// Returns `true` if the receive FIFO overflowed since this was last
+    /// checked.
+    fn rx_overrun(&mut self) -> bool;
+}
+
+impl Serial for MiniUart {
+    const RX_INTERRUPT: Interrupt = Interrupt::Aux;
+
+    fn new() -> MiniUart { MiniUart::new() }
+    unsafe fn from_initialized() -> MiniUart { MiniUart::from_initialized() }
+    fn has_byte(&self) -> bool { MiniUart::has_byte(self) }
+    fn read_byte(&mut self) -> u8 { MiniUart::read_byte(self) }
+    fn write_byte(&mut self, byte: u8) { MiniUart::write_byte(self, byte) }
+    fn set_rx_interrupt(&mut self, enabled: bool) { MiniUart::set_rx_interrupt(self, enabled) }
+    fn rx_overrun(&mut self) -> bool { MiniUart::rx_overrun(self) }
+}
+
+impl Serial for pl011::Uart {
+    const RX_INTERRUPT: Interrupt = Interrupt::Uart;
+
+    fn new() -> pl011::Uart { pl011::Uart::new() }
+    unsafe fn from_initialized() -> pl011::Uart { pl011::Uart::from_initialized() }
+    fn has_byte(&self) -> bool { pl011::Uart::has_byte(self) }
+    fn read_byte(&mut self) -> u8 { pl011::Uart::read_byte(self) }
+    fn write_byte(&mut self, byte: u8) { pl011::Uart::write_byte(self, byte) }
+    fn set_rx_interrupt(&mut self, enabled: bool) { pl011::Uart::set_rx_interrupt(self, enabled) }
+    fn rx_overrun(&mut self) -> bool { pl011::Uart::rx_overrun(self) }
+}
+
+/// The UART the kernel's console runs on. Switch to `pl011::Uart` to use the
+/// full UART instead, with `dtoverlay=pi3-miniuart-bt` in `config.txt`.
+pub type Uart = MiniUart;
+
+/// Bytes received by the UART interrupt handler, waiting to be read.
+static RX_BUFFER: RingBuffer = RingBuffer::new();
+
+/// Number of times the UART's receive FIFO overflowed before the interrupt
+/// handler drained it.
+static RX_FIFO_OVERRUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts of received data lost since boot.
+#[derive(Debug, Copy, Clone)]
+pub struct Overruns {
+    /// Times the UART's receive FIFO overflowed. Each may lose several bytes.
+    pub fifo: usize,
+    /// Bytes dropped because the receive buffer was full.
+    pub buffer: usize,
+}
 
-/// A global singleton allowing read/write access to the console.
-pub struct Console {
-    inner: Option<MiniUart>
+/// Returns the counts of received data lost since boot.
+pub fn rx_overruns() -> Overruns {
+    Overruns {
+        fifo: RX_FIFO_OVERRUNS.load(Relaxed),
+        buffer: RX_BUFFER.dropped(),
+    }
+}
+
+/// Drains the UART's receive FIFO into the receive buffer. Called by the IRQ
+/// handler when the UART interrupt is pending.
+pub fn handle_rx_interrupt() {
+    // The console owns the UART, but the handler can't take its lock: it may
+    // have interrupted the lock's holder. Only the data register is touched.
+    let mut uart = unsafe { Uart::from_initialized() };
+    if uart.rx_overrun() {
+        RX_FIFO_OVERRUNS.store(RX_FIFO_OVERRUNS.load(Relaxed) + 1, Relaxed);
+    }
+
+    while uart.has_byte() {
+        RX_BUFFER.push(uart.read_byte());
+    }
 }
 
-impl Console {
+/// A global singleton allowing read/write access to the console, on the UART
+/// `U`.
+pub struct Console<U> {
+    inner: Option<U>,
+    rx_interrupts: bool,
+    screen: Option<TextConsole<'static>>,
+}
+
+impl<U> Console<U> {
     /// Creates a new instance of `Console`.
-    const fn new() -> Console {
-        Console { inner: None }
+    const fn new() -> Console<U> {
+        Console { inner: None, rx_interrupts: false, screen: None }
     }
+}
 
+impl<U: Serial> Console<U> {
     /// Initializes the console if it's not already initialized.
     #[inline]
     fn initialize(&mut self) {
-        self.inner.get_or_insert_with(&MiniUart::new);
+        self.inner.get_or_insert_with(U::new);
     }
 
-    /// Returns a mutable borrow to the inner `MiniUart`, initializing it as
-    /// needed.
-    fn inner(&mut self) -> &mut MiniUart {
+    /// Returns a mutable borrow to the inner UART, initializing it as needed.
+    fn inner(&mut self) -> &mut U {
         self.initialize();
         self.inner.as_mut().unwrap()
     }
 
+    /// Switches the console to interrupt-driven receive: received bytes are
+    /// queued by the IRQ handler instead of being polled from the UART, so
+    /// none are lost while the CPU is busy. Unmasks IRQs.
+    ///
+    /// Reads no longer honor the UART's read timeout once this is enabled.
+    pub fn enable_rx_interrupts(&mut self) {
+        self.inner().set_rx_interrupt(true);
+        Controller::new().enable(U::RX_INTERRUPT);
+        self.rx_interrupts = true;
+        irq::enable();
+    }
+
+    /// Allocates a `width` by `height` framebuffer and mirrors everything
+    /// written to the console onto it from now on.
+    pub fn mirror_to_screen(&mut self, width: u32, height: u32) -> Result<(), mailbox::Error> {
+        let surface: Surface<'static> = Framebuffer::new(width, height)?.into_surface();
+        let white = framebuffer::rgb(0xFF, 0xFF, 0xFF);
+        self.screen = Some(TextConsole::new(surface, white, framebuffer::rgb(0, 0, 0)));
+        Ok(())
+    }
+
     /// Reads a byte from the UART device, blocking until a byte is available.
     pub fn read_byte(&mut self) -> u8 {
-        self.inner().read_byte()
+        if !self.rx_interrupts {
+            return self.inner().read_byte();
+        }
+
+        loop {
+            // Check with IRQs masked so a byte arriving between the check and
+            // `wfi` still wakes us up.
+            irq::disable();
+            let byte = RX_BUFFER.pop();
+            if byte.is_none() {
+                irq::wait_for_interrupt();
+            }
+            irq::enable();
+
+            if let Some(byte) = byte {
+                return byte;
+            }
+        }
     }
 
     /// Writes the byte `byte` to the UART device.
     pub fn write_byte(&mut self, byte: u8) {
+        if let Some(screen) = self.screen.as_mut() {
+            screen.write_byte(byte);
+        }
         self.inner().write_byte(byte)
     }
 }
 
-impl io::Read for Console {
+impl<U: Serial> io::Read for Console<U> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner().read(buf)
+        if !self.rx_interrupts {
+            return self.inner().read(buf);
+        }
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Wait for the first byte, then take whatever else has arrived.
+        buf[0] = self.read_byte();
+        let mut read = 1;
+        while read < buf.len() {
+            match RX_BUFFER.pop() {
+                Some(byte) => buf[read] = byte,
+                None => break,
+            }
+            read += 1;
+        }
+
+        Ok(read)
     }
 }
 
-impl io::Write for Console {
+impl<U: Serial> io::Write for Console<U> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(screen) = self.screen.as_mut() {
+            for byte in buf {
+                screen.write_byte(*byte);
+            }
+        }
         self.inner().write(buf)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+        self.inner().flush()
     }
 }
 
-impl fmt::Write for Console {
+impl<U: Serial> fmt::Write for Console<U> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(screen) = self.screen.as_mut() {
+            fmt::Write::write_str(screen, s)?;
+        }
         self.inner().write_str(s)
     }
 }
 
 /// Global `Console` singleton.
-/// Global `Console` singleton.
-pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
+pub static CONSOLE: Mutex<Console<Uart>> = Mutex::new(Console::new());
 
 /// Internal function called by the `kprint[ln]!` macros.
 #[doc(hidden)]
@@ -90,7 +270,7 @@ pub macro kprint($($arg:tt)*) {
 #[doc(hidden)]
 pub fn _noblock_print(args: fmt::Arguments) {
     use std::fmt::Write;
-    let mut console: Console = Console::new();
+    let mut console: Console<Uart> = Console::new();
     console.write_fmt(args).unwrap();
 }
 
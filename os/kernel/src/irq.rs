@@ -0,0 +1,33 @@
+use core::arch::asm;
+
+use pi::interrupt::Controller;
+
+use console::{self, Serial};
+
+/// Unmasks IRQs at the current exception level.
+pub fn enable() {
+    unsafe { asm!("msr daifclr, #2", options(nomem, nostack)) }
+}
+
+/// Masks IRQs at the current exception level.
+pub fn disable() {
+    unsafe { asm!("msr daifset, #2", options(nomem, nostack)) }
+}
+
+/// Waits for an interrupt. Returns when an interrupt is pending, even if IRQs
+/// are masked, so callers can mask IRQs, check their condition and then wait
+/// without missing a wakeup.
+pub fn wait_for_interrupt() {
+    unsafe { asm!("wfi", options(nomem, nostack)) }
+}
+
+/// Called by the exception vectors in `init.S` for every IRQ, with IRQs
+/// masked.
+#[no_mangle]
+pub extern "C" fn handle_irq() {
+    for int in Controller::new().pending() {
+        if int == console::Uart::RX_INTERRUPT {
+            console::handle_rx_interrupt()
+        }
+    }
+}
@@ -23,20 +23,31 @@ pub mod mutex;
 pub mod console;
 pub mod shell;
 pub mod allocator;
+pub mod ring_buffer;
+pub mod irq;
 
-use pi::{gpio::Gpio, timer, uart};
+use pi::{gpio::GpioBank, timer, uart};
+use pi::boot::Handoff;
 
 
 use allocator::Allocator;
+use mutex::Mutex;
 
 #[global_allocator]
 pub static ALLOCATOR: allocator::Allocator = Allocator::uninitialized();
 
+/// What the kernel was handed at entry. Set once at the start of `kmain`.
+pub static HANDOFF: Mutex<Option<Handoff>> = Mutex::new(None);
+
+/// Entry point, called by `init.S` with the handoff registers `x0` and `x1`.
 #[no_mangle]
-pub unsafe extern "C" fn kmain() {
+pub unsafe extern "C" fn kmain(tags: usize, boot_info: usize) {
     // FIXME: Start the shell.
+    // The boot info lives in memory the allocator is about to hand out.
+    *HANDOFF.lock() = Some(Handoff::from_registers(tags, boot_info));
     ALLOCATOR.initialize();
-    // let mut gpio_19 = Gpio::new(19).into_output();
+    console::CONSOLE.lock().enable_rx_interrupts();
+    // let mut gpio_19 = GpioBank::take().unwrap().pin(19).unwrap().into_output();
     // gpio_19.set();
     // timer::spin_sleep_ms(200);
     // gpio_19.clear();
@@ -0,0 +1,145 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+/// Number of slots in a `RingBuffer`. One slot is always left empty to tell a
+/// full buffer from an empty one.
+const CAPACITY: usize = 1024;
+
+/// A lock-free single-producer, single-consumer byte queue.
+///
+/// `push` may only be called from one context, such as an interrupt handler,
+/// and `pop` from one other. Each side only ever stores to its own index, so
+/// no read-modify-write atomics are needed; those don't work until the MMU
+/// and caches are enabled.
+pub struct RingBuffer {
+    buf: UnsafeCell<[u8; CAPACITY]>,
+    /// Index of the next byte to pop. Written only by the consumer.
+    head: AtomicUsize,
+    /// Index of the next free slot. Written only by the producer.
+    tail: AtomicUsize,
+    /// Number of bytes dropped because the buffer was full. Written only by
+    /// the producer.
+    dropped: AtomicUsize,
+}
+
+unsafe impl Sync for RingBuffer {}
+
+impl RingBuffer {
+    /// Returns a new, empty `RingBuffer`.
+    pub const fn new() -> RingBuffer {
+        RingBuffer {
+            buf: UnsafeCell::new([0; CAPACITY]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Appends `byte` to the buffer. Returns `false` and counts the byte as
+    /// dropped if the buffer is full.
+    ///
+    /// Must only be called by the producer.
+    pub fn push(&self, byte: u8) -> bool {
+        let tail = self.tail.load(Relaxed);
+        let next = (tail + 1) % CAPACITY;
+        if next == self.head.load(Acquire) {
+            self.dropped.store(self.dropped.load(Relaxed) + 1, Relaxed);
+            return false;
+        }
+
+        unsafe { (*self.buf.get())[tail] = byte };
+        self.tail.store(next, Release);
+        true
+    }
+
+    /// Removes and returns the oldest byte in the buffer, if any.
+    ///
+    /// Must only be called by the consumer.
+    pub fn pop(&self) -> Option<u8> {
+        let head = self.head.load(Relaxed);
+        if head == self.tail.load(Acquire) {
+            return None;
+        }
+
+        let byte = unsafe { (*self.buf.get())[head] };
+        self.head.store((head + 1) % CAPACITY, Release);
+        Some(byte)
+    }
+
+    /// Returns `true` if there are no bytes to pop.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Relaxed) == self.tail.load(Acquire)
+    }
+
+    /// Returns the number of bytes dropped because the buffer was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RingBuffer, CAPACITY};
+
+    #[test]
+    fn empty() {
+        let buffer = RingBuffer::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.pop(), None);
+        assert_eq!(buffer.dropped(), 0);
+    }
+
+    #[test]
+    fn fifo_across_wrap() {
+        let buffer = RingBuffer::new();
+
+        // Move both indices close to the end of the storage.
+        for _ in 0..CAPACITY - 3 {
+            assert!(buffer.push(0));
+            assert_eq!(buffer.pop(), Some(0));
+        }
+
+        for byte in 0..10 {
+            assert!(buffer.push(byte));
+        }
+        for byte in 0..10 {
+            assert_eq!(buffer.pop(), Some(byte));
+        }
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn capacity() {
+        let buffer = RingBuffer::new();
+        for i in 0..CAPACITY - 1 {
+            assert!(buffer.push(i as u8));
+        }
+        assert!(!buffer.push(0xFF));
+
+        for i in 0..CAPACITY - 1 {
+            assert_eq!(buffer.pop(), Some(i as u8));
+        }
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn dropped() {
+        let buffer = RingBuffer::new();
+        for _ in 0..CAPACITY - 1 {
+            buffer.push(0);
+        }
+        assert_eq!(buffer.dropped(), 0);
+
+        assert!(!buffer.push(1));
+        assert!(!buffer.push(2));
+        assert_eq!(buffer.dropped(), 2);
+
+        // Space freed by a pop is used again; the count isn't reset.
+        assert_eq!(buffer.pop(), Some(0));
+        assert!(buffer.push(3));
+        assert!(!buffer.push(4));
+        assert_eq!(buffer.dropped(), 3);
+    }
+}
@@ -1,8 +1,10 @@
-use console::{kprint, kprintln, CONSOLE};
+use console::{self, kprint, kprintln, CONSOLE};
+use pi::power;
 use pi::timer::spin_sleep_ms;
 use stack_vec::StackVec;
 use std::fmt;
 use std::fmt::Write;
+use std::io;
 use std::str;
 use std::string;
 use std::vec;
@@ -137,6 +139,49 @@ pub fn shell(prefix: &str) -> ! {
                             console.write_byte(b' ');
                         }
                     }
+                    "uartstat" => {
+                        let overruns = console::rx_overruns();
+                        write!(console, "fifo overruns: {}, buffer drops: {}",
+                               overruns.fifo, overruns.buffer).unwrap();
+                    }
+                    "reboot" => {
+                        let partition = match command.args.get(1) {
+                            Some(arg) => arg.parse::<u8>().ok().filter(|&p| p < power::HALT_PARTITION),
+                            None => Some(0),
+                        };
+                        match partition {
+                            Some(partition) => {
+                                console.write_str("rebooting...").unwrap();
+                                console.write_byte(ENTER);
+                                console.write_byte(NEWLINE);
+                                io::Write::flush(&mut *console).unwrap();
+                                power::reboot_to(partition);
+                            }
+                            None => console.write_str("usage: reboot [partition]").unwrap(),
+                        }
+                    }
+                    "screen" => {
+                        let size = match (command.args.get(1), command.args.get(2)) {
+                            (None, None) => Some((1024, 768)),
+                            (Some(width), Some(height)) => width.parse().ok().zip(height.parse().ok()),
+                            _ => None,
+                        };
+                        match size {
+                            Some((width, height)) => {
+                                if let Err(err) = console.mirror_to_screen(width, height) {
+                                    write!(console, "screen: framebuffer allocation failed: {:?}", err).unwrap();
+                                }
+                            }
+                            None => console.write_str("usage: screen [width height]").unwrap(),
+                        }
+                    }
+                    "halt" => {
+                        console.write_str("halting...").unwrap();
+                        console.write_byte(ENTER);
+                        console.write_byte(NEWLINE);
+                        io::Write::flush(&mut *console).unwrap();
+                        power::halt();
+                    }
                     _ => {
                         console.write_str("Unknown command").unwrap();
                     }
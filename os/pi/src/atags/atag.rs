@@ -2,13 +2,21 @@ use core::{ffi::{CStr, c_char}, str::from_utf8_unchecked};
 
 use atags::raw;
 
-pub use atags::raw::{Core, Mem};
+pub use atags::raw::{Core, Mem, VideoText, Ramdisk, Initrd2, VideoLfb};
 
 /// An ATAG.
 #[derive(Debug, Copy, Clone)]
 pub enum Atag {
     Core(raw::Core),
     Mem(raw::Mem),
+    VideoText(raw::VideoText),
+    Ramdisk(raw::Ramdisk),
+    Initrd2(raw::Initrd2),
+    /// The board's 64-bit serial number.
+    Serial(u64),
+    /// The board's revision code.
+    Revision(u32),
+    VideoLfb(raw::VideoLfb),
     Cmd(&'static str),
     Unknown(u32),
     None
@@ -31,6 +39,56 @@ impl Atag {
         }
     }
 
+    /// Returns `Some` if this is a `VideoText` ATAG. Otherwise returns `None`.
+    pub fn video_text(self) -> Option<VideoText> {
+        match self {
+            Atag::VideoText(video_text) => Some(video_text),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` if this is a `Ramdisk` ATAG. Otherwise returns `None`.
+    pub fn ramdisk(self) -> Option<Ramdisk> {
+        match self {
+            Atag::Ramdisk(ramdisk) => Some(ramdisk),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` if this is an `Initrd2` ATAG. Otherwise returns `None`.
+    pub fn initrd2(self) -> Option<Initrd2> {
+        match self {
+            Atag::Initrd2(initrd2) => Some(initrd2),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` with the serial number if this is a `Serial` ATAG.
+    /// Otherwise returns `None`.
+    pub fn serial(self) -> Option<u64> {
+        match self {
+            Atag::Serial(serial) => Some(serial),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` with the revision code if this is a `Revision` ATAG.
+    /// Otherwise returns `None`.
+    pub fn revision(self) -> Option<u32> {
+        match self {
+            Atag::Revision(revision) => Some(revision),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` if this is a `VideoLfb` ATAG. Otherwise returns `None`.
+    pub fn video_lfb(self) -> Option<VideoLfb> {
+        match self {
+            Atag::VideoLfb(video_lfb) => Some(video_lfb),
+            _ => None,
+        }
+    }
+
     /// Returns `Some` with the command line string if this is a `Cmd` ATAG.
     /// Otherwise returns `None`.
     pub fn cmd(self) -> Option<&'static str> {
@@ -53,6 +111,14 @@ impl<'a> From<&'a raw::Atag> for Atag {
             match (atag.tag, &atag.kind) {
                 (raw::Atag::CORE, &raw::Kind { core }) => Atag::Core(core),
                 (raw::Atag::MEM, &raw::Kind { mem }) => Atag::Mem(mem),
+                (raw::Atag::VIDEOTEXT, &raw::Kind { videotext }) => Atag::VideoText(videotext),
+                (raw::Atag::RAMDISK, &raw::Kind { ramdisk }) => Atag::Ramdisk(ramdisk),
+                (raw::Atag::INITRD2, &raw::Kind { initrd2 }) => Atag::Initrd2(initrd2),
+                (raw::Atag::SERIAL, &raw::Kind { serial }) => {
+                    Atag::Serial((serial.high as u64) << 32 | serial.low as u64)
+                },
+                (raw::Atag::REVISION, &raw::Kind { revision }) => Atag::Revision(revision.rev),
+                (raw::Atag::VIDEOLFB, &raw::Kind { videolfb }) => Atag::VideoLfb(videolfb),
                 (raw::Atag::CMDLINE, &raw::Kind { ref cmd }) => {
                     let cmdline = CStr::from_ptr((&cmd.cmd as *const u8) as *const c_char);
                     Atag::Cmd(std::str::from_utf8_unchecked(cmdline.to_bytes()))
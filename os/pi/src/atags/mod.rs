@@ -1,10 +1,13 @@
 mod raw;
 mod atag;
 
+#[cfg(test)]
+mod tests;
+
 pub use self::atag::*;
 
 /// The address at which the firmware loads the ATAGS.
-const ATAG_BASE: usize = 0x100;
+pub const ATAG_BASE: usize = 0x100;
 
 /// An iterator over the ATAGS on this system.
 pub struct Atags {
@@ -20,6 +23,55 @@ impl Atags {
             first: true,
         }
     }
+
+    /// Returns an iterator over the ATAGS at `base`, for when they weren't
+    /// left at `ATAG_BASE`. Returns `None` if `base` doesn't hold an ATAG
+    /// list, e.g. because the firmware passed a device tree instead.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to readable memory. If it holds an ATAG list, the
+    /// list must be `'static` and terminated by a `NONE` ATAG.
+    pub unsafe fn at(base: usize) -> Option<Atags> {
+        let ptr = &*(base as *const raw::Atag);
+        if ptr.tag != raw::Atag::CORE {
+            return None;
+        }
+
+        Some(Atags { ptr, first: true })
+    }
+}
+
+/// Appends an `INITRD2` ATAG describing the `size`-byte ramdisk at `start` to
+/// the ATAG list at `base`, moving the terminating `NONE` ATAG past it.
+///
+/// Returns `false` without writing anything if `base` doesn't hold an ATAG
+/// list, e.g. because the firmware passed a device tree instead.
+///
+/// # Safety
+///
+/// `base` must point to readable memory. If it holds an ATAG list, the list
+/// must be writable and its terminating `NONE` ATAG followed by at least 4
+/// free words.
+pub unsafe fn append_initrd2(base: usize, start: u32, size: u32) -> bool {
+    let mut tag = base as *mut raw::Atag;
+    if (*tag).tag != raw::Atag::CORE {
+        return false;
+    }
+
+    while (*tag).tag != raw::Atag::NONE {
+        tag = (tag as *mut u32).offset((*tag).dwords as isize) as *mut raw::Atag;
+    }
+
+    // Two header words followed by the two `Initrd2` words.
+    (*tag).dwords = 4;
+    (*tag).tag = raw::Atag::INITRD2;
+    (*tag).kind.initrd2 = raw::Initrd2 { start, size };
+
+    let end = (tag as *mut u32).offset(4) as *mut raw::Atag;
+    (*end).dwords = 0;
+    (*end).tag = raw::Atag::NONE;
+    true
 }
 
 impl Iterator for Atags {
@@ -41,6 +41,12 @@ impl Atag {
 pub union Kind {
     pub core: Core,
     pub mem: Mem,
+    pub videotext: VideoText,
+    pub ramdisk: Ramdisk,
+    pub initrd2: Initrd2,
+    pub serial: Serial,
+    pub revision: Revision,
+    pub videolfb: VideoLfb,
     pub cmd: Cmd
 }
 
@@ -61,6 +67,82 @@ pub struct Mem {
     pub start: u32
 }
 
+/// A `VIDEOTEXT` ATAG, describing a VGA text mode display.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VideoText {
+    pub x: u8,
+    pub y: u8,
+    pub video_page: u16,
+    pub video_mode: u8,
+    pub video_cols: u8,
+    pub video_ega_bx: u16,
+    pub video_lines: u8,
+    pub video_isvga: u8,
+    pub video_points: u16
+}
+
+/// A `RAMDISK` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Ramdisk {
+    /// Bit 0: load the ramdisk. Bit 1: prompt for the ramdisk.
+    pub flags: u32,
+    /// Decompressed size of the ramdisk in KiB.
+    pub size: u32,
+    /// Block the ramdisk image starts at.
+    pub start: u32
+}
+
+/// An `INITRD2` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Initrd2 {
+    /// Physical start address of the ramdisk.
+    pub start: u32,
+    /// Size of the ramdisk in bytes.
+    pub size: u32
+}
+
+/// A `SERIAL` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Serial {
+    pub low: u32,
+    pub high: u32
+}
+
+/// A `REVISION` ATAG.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Revision {
+    pub rev: u32
+}
+
+/// A `VIDEOLFB` ATAG, describing a linear framebuffer.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VideoLfb {
+    pub width: u16,
+    pub height: u16,
+    /// Bits per pixel.
+    pub depth: u16,
+    /// Bytes per row.
+    pub line_length: u16,
+    /// Physical address of the framebuffer.
+    pub base: u32,
+    /// Size of the framebuffer in bytes.
+    pub size: u32,
+    pub red_size: u8,
+    pub red_pos: u8,
+    pub green_size: u8,
+    pub green_pos: u8,
+    pub blue_size: u8,
+    pub blue_pos: u8,
+    pub rsvd_size: u8,
+    pub rsvd_pos: u8
+}
+
 /// A `CMDLINE` ATAG.
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
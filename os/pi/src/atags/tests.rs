@@ -0,0 +1,81 @@
+use std::vec::Vec;
+
+use super::{raw, Atag, Atags};
+
+/// Decodes the ATAG with id `tag` carrying `words`.
+fn decode(tag: u32, words: &[u32]) -> Atag {
+    // Large enough for any `raw::Kind`.
+    let mut buf = [0u32; 10];
+    buf[0] = 2 + words.len() as u32;
+    buf[1] = tag;
+    buf[2..2 + words.len()].copy_from_slice(words);
+    Atag::from(unsafe { &*(buf.as_ptr() as *const raw::Atag) })
+}
+
+#[test]
+fn list() {
+    static LIST: [u32; 15] = [
+        5, raw::Atag::CORE, 0, 4096, 0,
+        4, raw::Atag::MEM, 0x3B400000, 0,
+        4, raw::Atag::INITRD2, 0x2000000, 0x1234,
+        0, raw::Atag::NONE,
+    ];
+
+    let tags: Vec<Atag> = unsafe { Atags::at(LIST.as_ptr() as usize) }.unwrap().collect();
+    assert_eq!(tags.len(), 3);
+    assert_eq!(tags[0].core().map(|core| core.page_size), Some(4096));
+    assert_eq!(tags[1].mem().map(|mem| (mem.start, mem.size)), Some((0, 0x3B400000)));
+    assert_eq!(tags[2].initrd2().map(|initrd2| initrd2.start), Some(0x2000000));
+
+    let not_atags = [0xD00DFEEDu32.to_be(), 0];
+    assert!(unsafe { Atags::at(not_atags.as_ptr() as usize) }.is_none());
+}
+
+#[test]
+fn serial() {
+    let atag = decode(raw::Atag::SERIAL, &[0x89ABCDEF, 0x01234567]);
+    assert_eq!(atag.serial(), Some(0x0123456789ABCDEF));
+    assert!(atag.revision().is_none());
+}
+
+#[test]
+fn revision() {
+    assert_eq!(decode(raw::Atag::REVISION, &[0xA02082]).revision(), Some(0xA02082));
+}
+
+#[test]
+fn initrd2() {
+    let initrd2 = decode(raw::Atag::INITRD2, &[0x2000000, 0x1234]).initrd2().unwrap();
+    assert_eq!((initrd2.start, initrd2.size), (0x2000000, 0x1234));
+}
+
+#[test]
+fn ramdisk() {
+    let ramdisk = decode(raw::Atag::RAMDISK, &[1, 4096, 0]).ramdisk().unwrap();
+    assert_eq!((ramdisk.flags, ramdisk.size, ramdisk.start), (1, 4096, 0));
+}
+
+#[test]
+fn video_lfb() {
+    let words = [768 << 16 | 1024, 4096 << 16 | 32, 0x3C100000, 0x300000, 0x0808_1008, 0x1808_0008];
+    let lfb = decode(raw::Atag::VIDEOLFB, &words).video_lfb().unwrap();
+    assert_eq!((lfb.width, lfb.height, lfb.depth, lfb.line_length), (1024, 768, 32, 4096));
+    assert_eq!((lfb.base, lfb.size), (0x3C100000, 0x300000));
+    assert_eq!((lfb.red_size, lfb.red_pos, lfb.green_size, lfb.green_pos), (8, 16, 8, 8));
+    assert_eq!((lfb.blue_size, lfb.blue_pos, lfb.rsvd_size, lfb.rsvd_pos), (8, 0, 8, 24));
+}
+
+#[test]
+fn video_text() {
+    let text = decode(raw::Atag::VIDEOTEXT, &[0x0003_1950, 0x0000_5003, 0x0010_0119]).video_text().unwrap();
+    assert_eq!((text.x, text.y, text.video_cols, text.video_lines), (0x50, 0x19, 0x50, 0x19));
+    assert_eq!((text.video_mode, text.video_isvga, text.video_points), (3, 1, 16));
+}
+
+#[test]
+fn unknown() {
+    match decode(0x5441_0042, &[]) {
+        Atag::Unknown(0x5441_0042) => {}
+        atag => panic!("decoded {:?}", atag),
+    }
+}
@@ -0,0 +1,152 @@
+//! A common interface to sector-addressed storage.
+
+use std::io;
+
+#[cfg(test)]
+mod tests;
+
+/// The size of a sector on SD cards and in most disk images, in bytes.
+pub const SECTOR_SIZE: usize = 512;
+
+/// A device that reads and writes fixed-size sectors.
+///
+/// Sectors are numbered from `0`. Implementors must override at least
+/// `read_sector()` and `write_sector()`; the multi-sector methods default to
+/// transferring one sector at a time.
+pub trait BlockDevice {
+    /// Returns the size of a sector in bytes. Defaults to `SECTOR_SIZE`.
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    /// Reads sector `n` into `buf`, returning the number of bytes read.
+    ///
+    /// Only the first `sector_size()` bytes of `buf` are written. If `buf` is
+    /// shorter than a sector, only `buf.len()` bytes are read.
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Writes `buf` to sector `n`, returning the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if `buf` is shorter than a sector.
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize>;
+
+    /// Reads the sectors starting at `start` into `buf`, which must hold a
+    /// whole number of sectors. Returns the number of bytes read.
+    fn read_sectors(&mut self, start: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.sector_size();
+        check_length(buf.len(), size)?;
+        for (i, chunk) in buf.chunks_mut(size).enumerate() {
+            self.read_sector(start + i as u64, chunk)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Writes `buf`, which must hold a whole number of sectors, to the
+    /// sectors starting at `start`. Returns the number of bytes written.
+    fn write_sectors(&mut self, start: u64, buf: &[u8]) -> io::Result<usize> {
+        let size = self.sector_size();
+        check_length(buf.len(), size)?;
+        for (i, chunk) in buf.chunks(size).enumerate() {
+            self.write_sector(start + i as u64, chunk)?;
+        }
+        Ok(buf.len())
+    }
+}
+
+impl<'a, T: BlockDevice + ?Sized> BlockDevice for &'a mut T {
+    fn sector_size(&self) -> usize {
+        (**self).sector_size()
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_sector(n, buf)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        (**self).write_sector(n, buf)
+    }
+
+    fn read_sectors(&mut self, start: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_sectors(start, buf)
+    }
+
+    fn write_sectors(&mut self, start: u64, buf: &[u8]) -> io::Result<usize> {
+        (**self).write_sectors(start, buf)
+    }
+}
+
+/// Returns an `InvalidInput` error unless `len` is a whole number of
+/// `size`-byte sectors.
+pub(crate) fn check_length(len: usize, size: usize) -> io::Result<()> {
+    if len % size != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "buffer is not a whole number of sectors"));
+    }
+
+    Ok(())
+}
+
+/// A disk image held in any seekable stream, such as a file, viewed as a
+/// block device.
+///
+/// This lets tools and tests exercise code written against `BlockDevice`
+/// without real hardware.
+#[derive(Debug)]
+pub struct Image<T> {
+    inner: T,
+    sector_size: usize,
+}
+
+impl<T: io::Read + io::Write + io::Seek> Image<T> {
+    /// Returns a block device over `inner` with `SECTOR_SIZE`-byte sectors.
+    pub fn new(inner: T) -> Image<T> {
+        Image::with_sector_size(inner, SECTOR_SIZE)
+    }
+
+    /// Returns a block device over `inner` with `sector_size`-byte sectors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sector_size` is `0`.
+    pub fn with_sector_size(inner: T, sector_size: usize) -> Image<T> {
+        assert!(sector_size > 0, "Image::with_sector_size(): sector size is 0");
+        Image { inner, sector_size }
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Moves the stream to the start of sector `n`.
+    fn seek(&mut self, n: u64) -> io::Result<()> {
+        self.inner.seek(io::SeekFrom::Start(n * self.sector_size as u64))?;
+        Ok(())
+    }
+}
+
+impl<T: io::Read + io::Write + io::Seek> BlockDevice for Image<T> {
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.sector_size);
+        self.seek(n)?;
+        self.inner.read_exact(&mut buf[..len])?;
+        Ok(len)
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() < self.sector_size {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "buffer is shorter than a sector"));
+        }
+
+        self.seek(n)?;
+        self.inner.write_all(&buf[..self.sector_size])?;
+        Ok(self.sector_size)
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{self, Cursor};
+
+use super::{BlockDevice, Image, SECTOR_SIZE};
+
+/// Returns an image over `storage` in which every byte of sector `i` is `i`.
+fn numbered<'a>(storage: &'a mut [u8]) -> Image<Cursor<&'a mut [u8]>> {
+    for (i, sector) in storage.chunks_mut(SECTOR_SIZE).enumerate() {
+        for byte in sector.iter_mut() {
+            *byte = i as u8;
+        }
+    }
+
+    Image::new(Cursor::new(storage))
+}
+
+#[test]
+fn read_sector() {
+    let mut storage = [0u8; 4 * SECTOR_SIZE];
+    let mut image = numbered(&mut storage);
+
+    let mut buf = [0xFFu8; SECTOR_SIZE + 16];
+    assert_eq!(image.read_sector(2, &mut buf).unwrap(), SECTOR_SIZE);
+    assert!(buf[..SECTOR_SIZE].iter().all(|&b| b == 2));
+    assert!(buf[SECTOR_SIZE..].iter().all(|&b| b == 0xFF));
+
+    let mut short = [0u8; 16];
+    assert_eq!(image.read_sector(3, &mut short).unwrap(), 16);
+    assert!(short.iter().all(|&b| b == 3));
+}
+
+#[test]
+fn read_past_end() {
+    let mut storage = [0u8; 2 * SECTOR_SIZE];
+    let mut image = numbered(&mut storage);
+
+    let mut buf = [0u8; SECTOR_SIZE];
+    let error = image.read_sector(2, &mut buf).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn write_sector() {
+    let mut storage = [0u8; 3 * SECTOR_SIZE];
+    {
+        let mut image = numbered(&mut storage);
+        assert_eq!(image.write_sector(1, &[0xAB; SECTOR_SIZE]).unwrap(), SECTOR_SIZE);
+
+        let error = image.write_sector(0, &[0xCD; 16]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    assert!(storage[..SECTOR_SIZE].iter().all(|&b| b == 0));
+    assert!(storage[SECTOR_SIZE..2 * SECTOR_SIZE].iter().all(|&b| b == 0xAB));
+    assert!(storage[2 * SECTOR_SIZE..].iter().all(|&b| b == 2));
+}
+
+#[test]
+fn multiple_sectors() {
+    let mut storage = [0u8; 4 * SECTOR_SIZE];
+    let mut image = numbered(&mut storage);
+
+    let mut buf = [0u8; 2 * SECTOR_SIZE];
+    assert_eq!(image.read_sectors(1, &mut buf).unwrap(), 2 * SECTOR_SIZE);
+    assert!(buf[..SECTOR_SIZE].iter().all(|&b| b == 1));
+    assert!(buf[SECTOR_SIZE..].iter().all(|&b| b == 2));
+
+    for byte in buf.iter_mut() {
+        *byte = 0x5A;
+    }
+    // Through a `&mut` to check the forwarding impl.
+    assert_eq!((&mut image).write_sectors(2, &buf).unwrap(), 2 * SECTOR_SIZE);
+
+    let mut sector = [0u8; SECTOR_SIZE];
+    image.read_sector(3, &mut sector).unwrap();
+    assert!(sector.iter().all(|&b| b == 0x5A));
+
+    let error = image.read_sectors(0, &mut buf[..SECTOR_SIZE + 1]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn custom_sector_size() {
+    let mut storage = [0u8; 64];
+    let mut image = Image::with_sector_size(Cursor::new(&mut storage[..]), 16);
+    assert_eq!(image.sector_size(), 16);
+
+    image.write_sector(2, &[7; 16]).unwrap();
+    let storage = image.into_inner().into_inner();
+    assert!(storage[32..48].iter().all(|&b| b == 7));
+    assert!(storage[48..].iter().all(|&b| b == 0));
+}
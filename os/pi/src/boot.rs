@@ -0,0 +1,105 @@
+/// Value of `BootInfo::magic` for a valid structure: `BOOT` in ASCII.
+pub const BOOT_INFO_MAGIC: u32 = 0x544F4F42;
+
+/// Where the loaded image came from.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadSource {
+    Unknown = 0,
+    Uart = 1,
+    Sd = 2,
+}
+
+/// Information about how the kernel was loaded, filled in by the bootloader.
+///
+/// The bootloader enters the kernel with `x0` holding the address of the
+/// ATAGs (or device tree) and `x1` holding the address of this structure.
+/// The structure lives in the bootloader's memory, which the kernel is free to
+/// reuse, so it should be copied out before any allocator is initialized.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct BootInfo {
+    /// Always `BOOT_INFO_MAGIC`.
+    pub magic: u32,
+    /// Size of this structure in bytes, for forwards compatibility.
+    pub size: u32,
+    /// Size of the loaded image in bytes.
+    pub image_size: u64,
+    /// Where the image was loaded from.
+    pub source: LoadSource,
+    /// Bootloader version as `major << 16 | minor << 8 | patch`.
+    pub loader_version: u32,
+}
+
+impl BootInfo {
+    /// Returns a new `BootInfo` for an image of `image_size` bytes loaded
+    /// from `source` by a bootloader of version `loader_version`.
+    pub fn new(image_size: u64, source: LoadSource, loader_version: u32) -> BootInfo {
+        BootInfo {
+            magic: BOOT_INFO_MAGIC,
+            size: ::core::mem::size_of::<BootInfo>() as u32,
+            image_size,
+            source,
+            loader_version,
+        }
+    }
+
+    /// Returns a copy of the `BootInfo` at `addr` if there is a valid one
+    /// there. Returns `None` if `addr` is `0` or the magic doesn't match.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be `0` or point to readable memory at least
+    /// `size_of::<BootInfo>()` bytes long.
+    pub unsafe fn from_addr(addr: usize) -> Option<BootInfo> {
+        if addr == 0 || addr % ::core::mem::align_of::<BootInfo>() != 0 {
+            return None;
+        }
+
+        // Check the fields as plain integers before reading an enum out.
+        let ptr = addr as *const BootInfo;
+        let magic = *(ptr as *const u32);
+        let source = *(::core::ptr::addr_of!((*ptr).source) as *const u32);
+        if magic != BOOT_INFO_MAGIC || source > LoadSource::Sd as u32 {
+            return None;
+        }
+
+        Some(*ptr)
+    }
+
+    /// Returns the bootloader version as `(major, minor, patch)`.
+    pub fn version(&self) -> (u16, u8, u8) {
+        let v = self.loader_version;
+        ((v >> 16) as u16, (v >> 8) as u8, v as u8)
+    }
+}
+
+/// State handed to the kernel in registers `x0` and `x1` at entry.
+#[derive(Debug, Copy, Clone)]
+pub struct Handoff {
+    /// Address of the ATAGs or device tree.
+    pub tags: usize,
+    /// Boot information, if the kernel was chain-loaded by the bootloader.
+    pub info: Option<BootInfo>,
+}
+
+impl Handoff {
+    /// Interprets the values of `x0` and `x1` the kernel was entered with.
+    ///
+    /// # Safety
+    ///
+    /// `x0` and `x1` must be the unmodified values the kernel was entered
+    /// with.
+    pub unsafe fn from_registers(x0: usize, x1: usize) -> Handoff {
+        Handoff {
+            tags: x0,
+            info: BootInfo::from_addr(x1),
+        }
+    }
+
+    /// Returns `true` if the kernel was chain-loaded by the bootloader rather
+    /// than started directly by the firmware.
+    pub fn chain_loaded(&self) -> bool {
+        self.info.is_some()
+    }
+}
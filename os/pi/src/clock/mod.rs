@@ -0,0 +1,249 @@
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use common::IO_BASE;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the clock manager registers.
+const CM_BASE: usize = IO_BASE + 0x101000;
+
+/// Every write to a clock manager register must carry this in its top byte.
+const PASSWORD: u32 = 0x5A << 24;
+
+/// Largest integer part of a divisor.
+const MAX_INTEGER: u32 = 0xFFF;
+
+/// Bit fields of a `CTL` register.
+#[repr(u32)]
+enum Control {
+    Source = 0xF,
+    Enable = 1 << 4,
+    Kill = 1 << 5,
+    Busy = 1 << 7,
+}
+
+/// Shift of the MASH field of `CTL`.
+const CONTROL_MASH_SHIFT: u32 = 9;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTL: Volatile<u32>,
+    DIV: Volatile<u32>,
+}
+
+/// A clock generated by the clock manager.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockId {
+    /// General purpose clock 0, on GPIO pin 4.
+    Gp0,
+    /// General purpose clock 1, on GPIO pin 5.
+    Gp1,
+    /// General purpose clock 2, on GPIO pin 6.
+    Gp2,
+    /// Bit clock of the PCM/I2S audio interface.
+    Pcm,
+    /// Clock of the PWM controller. See `pwm::Pwm`.
+    Pwm,
+}
+
+impl ClockId {
+    /// Returns the offset of the clock's `CTL` register.
+    fn offset(self) -> usize {
+        match self {
+            ClockId::Gp0 => 0x70,
+            ClockId::Gp1 => 0x78,
+            ClockId::Gp2 => 0x80,
+            ClockId::Pcm => 0x98,
+            ClockId::Pwm => 0xA0,
+        }
+    }
+}
+
+/// The clock a generated clock is divided down from.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// No clock: the generator stops.
+    Ground = 0,
+    /// The 19.2MHz crystal oscillator.
+    Oscillator = 1,
+    /// The PLL clocking the ARM cores, whose rate follows `arm_freq`.
+    PllA = 4,
+    /// Also clocks the VPU core, so its rate follows `core_freq`.
+    PllC = 5,
+    /// Fixed at 500MHz.
+    PllD = 6,
+    /// The HDMI auxiliary clock, which only runs while HDMI is in use.
+    HdmiAux = 7,
+}
+
+impl Source {
+    /// Returns the rate of the source in Hz, if it is fixed.
+    pub fn frequency(self) -> Option<u32> {
+        match self {
+            Source::Oscillator => Some(19_200_000),
+            Source::PllD => Some(500_000_000),
+            _ => None,
+        }
+    }
+}
+
+/// The noise-shaping (MASH) filter order, which spreads the fractional part
+/// of a divisor over time. Order `0` uses the integer part only.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mash {
+    Integer = 0,
+    One = 1,
+    Two = 2,
+    Three = 3,
+}
+
+impl Mash {
+    /// Returns the smallest integer part of a divisor the filter works with.
+    pub fn min_integer(self) -> u16 {
+        match self {
+            Mash::Integer => 1,
+            Mash::One => 2,
+            Mash::Two => 3,
+            Mash::Three => 5,
+        }
+    }
+}
+
+/// A clock divisor with a 12-bit integer and a 12-bit fractional part, in
+/// 4096ths.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Divisor {
+    pub integer: u16,
+    pub fraction: u16,
+}
+
+impl Divisor {
+    /// Returns the divisor bringing `source` Hz closest to `target` Hz.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `target` is `0` or can't be reached with a divisor between 1
+    /// and 4095.
+    pub fn for_frequency(source: u32, target: u32) -> Divisor {
+        assert!(target > 0, "Divisor::for_frequency(): target is 0");
+        let scaled = ((source as u64) << 12) + target as u64 / 2;
+        let scaled = scaled / target as u64;
+        let integer = scaled >> 12;
+        if integer == 0 || integer > MAX_INTEGER as u64 {
+            panic!("Divisor: {} Hz unreachable from {} Hz", target, source);
+        }
+
+        Divisor { integer: integer as u16, fraction: (scaled & 0xFFF) as u16 }
+    }
+
+    /// Returns the divisor and filter `Clock::set_frequency()` programs for
+    /// this divisor: the first-order filter for a fractional divisor, or the
+    /// nearest whole divisor without a filter if the integer part is too
+    /// small for one.
+    fn with_mash(self) -> (Divisor, Mash) {
+        if self.fraction == 0 {
+            (self, Mash::Integer)
+        } else if self.integer >= Mash::One.min_integer() {
+            (self, Mash::One)
+        } else {
+            let integer = self.integer + (self.fraction >= 0x800) as u16;
+            (Divisor { integer, fraction: 0 }, Mash::Integer)
+        }
+    }
+
+    /// Returns the rate `source` Hz is divided to with `mash`.
+    pub fn frequency(&self, source: u32, mash: Mash) -> u32 {
+        match mash {
+            Mash::Integer => source / self.integer as u32,
+            _ => (((source as u64) << 12) / self.bits() as u64) as u32,
+        }
+    }
+
+    /// Returns the divisor as laid out in a `DIV` register.
+    fn bits(&self) -> u32 {
+        (self.integer as u32 & MAX_INTEGER) << 12 | (self.fraction as u32 & 0xFFF)
+    }
+}
+
+/// A clock generated by the clock manager.
+///
+/// Nothing stops two `Clock`s for the same `ClockId` from existing; the
+/// driver using a clock, such as `pwm::Pwm`, owns it by convention.
+pub struct Clock {
+    registers: &'static mut Registers,
+}
+
+impl Clock {
+    /// Returns a handle to the clock `id`.
+    pub fn new(id: ClockId) -> Clock {
+        Clock {
+            registers: unsafe { &mut *((CM_BASE + id.offset()) as *mut Registers) },
+        }
+    }
+
+    /// Returns `true` while the clock generator is running.
+    pub fn is_busy(&self) -> bool {
+        self.registers.CTL.has_mask(Control::Busy as u32)
+    }
+
+    /// Stops the clock, waiting for the generator to finish its current
+    /// cycle. A generator that doesn't stop is killed.
+    pub fn stop(&mut self) {
+        let control = self.registers.CTL.read() & !(Control::Enable as u32 | 0xFF << 24);
+        self.registers.CTL.write(PASSWORD | control);
+        for _ in 0..100_000 {
+            if !self.is_busy() {
+                return;
+            }
+        }
+
+        self.registers.CTL.write(PASSWORD | control | Control::Kill as u32);
+        while self.is_busy() { }
+        self.registers.CTL.write(PASSWORD | control);
+    }
+
+    /// Restarts the clock from `source` divided by `divisor`, filtered by
+    /// `mash`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the integer part of `divisor` is below the minimum for
+    /// `mash`. See `Mash::min_integer()`.
+    pub fn start(&mut self, source: Source, divisor: Divisor, mash: Mash) {
+        assert!(divisor.integer >= mash.min_integer(),
+                "Clock::start(): divisor {} too small for {:?}", divisor.integer, mash);
+
+        self.stop();
+        self.registers.DIV.write(PASSWORD | divisor.bits());
+
+        // The source must settle before the generator is enabled.
+        let control = (mash as u32) << CONTROL_MASH_SHIFT | (source as u32 & Control::Source as u32);
+        self.registers.CTL.write(PASSWORD | control);
+        self.registers.CTL.write(PASSWORD | control | Control::Enable as u32);
+    }
+
+    /// Restarts the clock at the rate closest to `hz` that `source` can be
+    /// divided to, using the fractional divisor with a first-order filter.
+    /// Divisors below 2, too small for the filter, are rounded to a whole
+    /// divisor instead. Returns the rate set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source` doesn't have a fixed rate or `hz` is unreachable
+    /// from it. See `Divisor::for_frequency()`.
+    pub fn set_frequency(&mut self, source: Source, hz: u32) -> u32 {
+        let rate = match source.frequency() {
+            Some(rate) => rate,
+            None => panic!("Clock::set_frequency(): {:?} has no fixed rate", source),
+        };
+
+        let (divisor, mash) = Divisor::for_frequency(rate, hz).with_mash();
+        self.start(source, divisor, mash);
+        divisor.frequency(rate, mash)
+    }
+}
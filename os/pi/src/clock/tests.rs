@@ -0,0 +1,68 @@
+use super::{Divisor, Mash};
+
+const OSCILLATOR: u32 = 19_200_000;
+const PLLD: u32 = 500_000_000;
+
+#[test]
+fn whole_divisor() {
+    let divisor = Divisor::for_frequency(OSCILLATOR, 4_800_000);
+    assert_eq!(divisor, Divisor { integer: 4, fraction: 0 });
+    assert_eq!(divisor.bits(), 4 << 12);
+    assert_eq!(divisor.frequency(OSCILLATOR, Mash::Integer), 4_800_000);
+    assert_eq!(divisor.with_mash(), (divisor, Mash::Integer));
+}
+
+#[test]
+fn fractional_divisor() {
+    // 64 times the 44.1kHz audio sample rate.
+    let divisor = Divisor::for_frequency(OSCILLATOR, 44_100 * 64);
+    assert_eq!(divisor, Divisor { integer: 6, fraction: 3288 });
+    assert_eq!(divisor.bits(), 6 << 12 | 3288);
+    assert_eq!(divisor.frequency(OSCILLATOR, Mash::One), 2_822_394);
+    assert_eq!(divisor.frequency(OSCILLATOR, Mash::Integer), OSCILLATOR / 6);
+    assert_eq!(divisor.with_mash(), (divisor, Mash::One));
+}
+
+#[test]
+fn small_divisor() {
+    // A divisor of 1.67 is too small for MASH, so the nearest whole divisor
+    // is used instead.
+    let divisor = Divisor::for_frequency(PLLD, 300_000_000);
+    assert_eq!(divisor, Divisor { integer: 1, fraction: 2731 });
+    assert_eq!(divisor.with_mash(), (Divisor { integer: 2, fraction: 0 }, Mash::Integer));
+
+    let divisor = Divisor { integer: 1, fraction: 0x7FF };
+    assert_eq!(divisor.with_mash(), (Divisor { integer: 1, fraction: 0 }, Mash::Integer));
+}
+
+#[test]
+fn min_integer() {
+    assert_eq!(Mash::Integer.min_integer(), 1);
+    assert_eq!(Mash::One.min_integer(), 2);
+    assert_eq!(Mash::Two.min_integer(), 3);
+    assert_eq!(Mash::Three.min_integer(), 5);
+}
+
+#[test]
+fn max_divisor() {
+    let divisor = Divisor::for_frequency(4095 * 1_000, 1_000);
+    assert_eq!(divisor.bits(), 0xFFF << 12);
+}
+
+#[test]
+#[should_panic]
+fn too_slow() {
+    Divisor::for_frequency(OSCILLATOR, 4_000);
+}
+
+#[test]
+#[should_panic]
+fn too_fast() {
+    Divisor::for_frequency(OSCILLATOR, 40_000_000);
+}
+
+#[test]
+#[should_panic]
+fn zero_target() {
+    Divisor::for_frequency(OSCILLATOR, 0);
+}
@@ -5,3 +5,32 @@ pub const IO_BASE: usize = 0x3F000000;
 pub macro states($($name:ident),*) {
     $(pub enum $name {  })*
 }
+
+/// The address where the ARM local peripherals (core timers, mailboxes and
+/// the per-core interrupt routing) are mapped to.
+pub const LOCAL_BASE: usize = 0x40000000;
+
+/// The VideoCore bus address where I/O peripherals are mapped to. DMA
+/// addresses peripherals at bus addresses.
+pub const IO_BUS_BASE: u32 = 0x7E000000;
+
+/// Returns the VideoCore bus address of the peripheral register at ARM
+/// physical address `addr`.
+pub fn io_bus_address(addr: usize) -> u32 {
+    (addr - IO_BASE) as u32 + IO_BUS_BASE
+}
+
+/// Offset of the VideoCore bus alias of RAM that bypasses the VideoCore's L2
+/// cache. Addresses of RAM handed to the GPU or to DMA are bus addresses.
+pub const BUS_RAM_BASE: u32 = 0xC0000000;
+
+/// Returns the VideoCore bus address of the RAM at ARM physical address `addr`.
+pub fn bus_address(addr: usize) -> u32 {
+    addr as u32 | BUS_RAM_BASE
+}
+
+/// Returns the ARM physical address of the RAM at VideoCore bus address
+/// `addr`.
+pub fn arm_address(addr: u32) -> usize {
+    (addr & !BUS_RAM_BASE) as usize
+}
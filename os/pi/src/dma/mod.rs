@@ -0,0 +1,466 @@
+//! The BCM2837 DMA engine: chains of control blocks copying memory, filling
+//! it, or moving data to and from peripherals paced by their DREQ signals.
+//!
+//! Buffers are borrowed by the `ControlBlock`s describing them, and the
+//! control blocks by the channel running them, so neither can be touched or
+//! freed while the engine uses them. Transfers read and write RAM behind the
+//! CPU's back: with the data cache enabled, buffers must be cleaned before and
+//! invalidated after a transfer.
+
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{fence, AtomicU16, Ordering};
+use core::sync::atomic::Ordering::Relaxed;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+use common::{IO_BASE, bus_address, io_bus_address};
+use interrupt::Interrupt;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the DMA channel registers.
+const DMA_BASE: usize = IO_BASE + 0x7000;
+
+/// The address of the registers shared by all channels.
+const GLOBAL_BASE: usize = DMA_BASE + 0xFE0;
+
+/// Channels the firmware leaves to the ARM: 0, 2, 4, 5 and 8 to 14.
+pub const AVAILABLE: u16 = 0x7F35;
+
+/// The first "lite" channel. Lite channels move less data per cycle and at
+/// most `LITE_MAX_LENGTH` bytes per control block.
+const FIRST_LITE: u8 = 7;
+
+/// Longest transfer of a control block on a full channel, in bytes.
+const MAX_LENGTH: usize = (1 << 30) - 1;
+
+/// Longest transfer of a control block on a lite channel, in bytes.
+const LITE_MAX_LENGTH: usize = 0xFFFF;
+
+/// How long to wait for an aborted control block to stop, in spins.
+const ABORT_SPINS: usize = 100_000;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CS: Volatile<u32>,
+    CONBLK_AD: Volatile<u32>,
+    TI: ReadVolatile<u32>,
+    SOURCE_AD: ReadVolatile<u32>,
+    DEST_AD: ReadVolatile<u32>,
+    TXFR_LEN: ReadVolatile<u32>,
+    STRIDE: ReadVolatile<u32>,
+    NEXTCONBK: Volatile<u32>,
+    DEBUG: Volatile<u32>,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct GlobalRegisters {
+    INT_STATUS: Volatile<u32>,
+    __r0: [Reserved<u32>; 3],
+    ENABLE: Volatile<u32>,
+}
+
+/// Bit fields of the `CS` register.
+#[repr(u32)]
+enum Status {
+    Active = 1 << 0,
+    End = 1 << 1,
+    Interrupt = 1 << 2,
+    Error = 1 << 8,
+    /// Priority 8 for normal and 15 for panicking AXI transactions.
+    Priorities = 8 << 16 | 15 << 20,
+    WaitForOutstandingWrites = 1 << 28,
+    Abort = 1 << 30,
+    Reset = 1 << 31,
+}
+
+/// Bit fields of the `TI` word of a control block.
+#[repr(u32)]
+enum Info {
+    InterruptEnable = 1 << 0,
+    WaitForResponse = 1 << 3,
+    DestIncrement = 1 << 4,
+    DestDreq = 1 << 6,
+    SourceIncrement = 1 << 8,
+    SourceDreq = 1 << 10,
+}
+
+/// Shift of the peripheral map field of `TI`.
+const INFO_PERMAP_SHIFT: u32 = 16;
+
+/// The error bits of the `DEBUG` register: read last not set, FIFO and read
+/// errors. Write 1 to clear.
+const DEBUG_ERRORS: u32 = 0b111;
+
+/// A peripheral whose data register a channel can feed or drain, paced by
+/// the peripheral's DREQ signal so it never overflows or underruns.
+///
+/// The peripheral must have its DMA requests enabled separately.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Peripheral {
+    /// The PL011 UART's transmit FIFO. Each word carries one byte.
+    UartTx,
+    /// The PL011 UART's receive FIFO. Each word carries one byte and its
+    /// error flags.
+    UartRx,
+    /// The SPI0 FIFO, transmitting. Each word carries four bytes.
+    SpiTx,
+    /// The SPI0 FIFO, receiving. Each word carries four bytes.
+    SpiRx,
+    /// The EMMC controller's data register.
+    Emmc,
+    /// The PWM FIFO.
+    Pwm,
+}
+
+impl Peripheral {
+    /// Returns the DREQ number of the peripheral, as used in `TI.PERMAP`.
+    pub fn dreq(self) -> u32 {
+        match self {
+            Peripheral::Pwm => 5,
+            Peripheral::SpiTx => 6,
+            Peripheral::SpiRx => 7,
+            Peripheral::Emmc => 11,
+            Peripheral::UartTx => 12,
+            Peripheral::UartRx => 14,
+        }
+    }
+
+    /// Returns the ARM physical address of the peripheral's data register.
+    fn register(self) -> usize {
+        match self {
+            Peripheral::UartTx | Peripheral::UartRx => IO_BASE + 0x201000,
+            Peripheral::SpiTx | Peripheral::SpiRx => IO_BASE + 0x204004,
+            Peripheral::Emmc => IO_BASE + 0x300020,
+            Peripheral::Pwm => IO_BASE + 0x20C018,
+        }
+    }
+}
+
+/// Errors from a DMA transfer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A control block moves more data than the channel can in one go.
+    TooLong,
+    /// The channel reported an AXI error. Holds the error bits of `DEBUG`.
+    Bus(u32),
+}
+
+/// A DMA control block: one transfer, in the layout the engine reads.
+///
+/// Blocks are 32-byte aligned, as the engine requires, and borrow the
+/// buffers they transfer for `'a`.
+#[repr(C, align(32))]
+#[derive(Debug)]
+pub struct ControlBlock<'a> {
+    info: u32,
+    source: u32,
+    dest: u32,
+    length: u32,
+    stride: u32,
+    next: u32,
+    __reserved: [u32; 2],
+    _buffers: PhantomData<&'a mut [u32]>,
+}
+
+impl<'a> ControlBlock<'a> {
+    /// Returns a control block with the given `TI`, addresses and length.
+    fn new(info: u32, source: u32, dest: u32, words: usize) -> ControlBlock<'a> {
+        let length = words * mem::size_of::<u32>();
+        assert!(length <= MAX_LENGTH, "ControlBlock: {} bytes is too long", length);
+
+        ControlBlock {
+            info: info | Info::WaitForResponse as u32,
+            source,
+            dest,
+            length: length as u32,
+            stride: 0,
+            next: 0,
+            __reserved: [0; 2],
+            _buffers: PhantomData,
+        }
+    }
+
+    /// Returns a control block copying `src` to `dst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices' lengths differ or exceed 1GiB.
+    pub fn copy(src: &'a [u32], dst: &'a mut [u32]) -> ControlBlock<'a> {
+        assert_eq!(src.len(), dst.len(), "ControlBlock::copy(): lengths differ");
+        let info = Info::SourceIncrement as u32 | Info::DestIncrement as u32;
+        ControlBlock::new(info, bus_address(src.as_ptr() as usize),
+                          bus_address(dst.as_mut_ptr() as usize), dst.len())
+    }
+
+    /// Returns a control block setting every word of `dst` to `*value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dst` exceeds 1GiB.
+    pub fn fill(value: &'a u32, dst: &'a mut [u32]) -> ControlBlock<'a> {
+        ControlBlock::new(Info::DestIncrement as u32, bus_address(value as *const u32 as usize),
+                          bus_address(dst.as_mut_ptr() as usize), dst.len())
+    }
+
+    /// Returns a control block writing `src` to the data register of
+    /// `peripheral` as fast as it accepts it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` exceeds 1GiB.
+    pub fn to_peripheral(src: &'a [u32], peripheral: Peripheral) -> ControlBlock<'a> {
+        let info = Info::SourceIncrement as u32 | Info::DestDreq as u32
+            | peripheral.dreq() << INFO_PERMAP_SHIFT;
+        ControlBlock::new(info, bus_address(src.as_ptr() as usize),
+                          io_bus_address(peripheral.register()), src.len())
+    }
+
+    /// Returns a control block filling `dst` from the data register of
+    /// `peripheral` as fast as it provides data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dst` exceeds 1GiB.
+    pub fn from_peripheral(peripheral: Peripheral, dst: &'a mut [u32]) -> ControlBlock<'a> {
+        let info = Info::DestIncrement as u32 | Info::SourceDreq as u32
+            | peripheral.dreq() << INFO_PERMAP_SHIFT;
+        ControlBlock::new(info, io_bus_address(peripheral.register()),
+                          bus_address(dst.as_mut_ptr() as usize), dst.len())
+    }
+
+    /// Makes the channel raise its interrupt once this block completes.
+    pub fn with_interrupt(mut self) -> ControlBlock<'a> {
+        self.info |= Info::InterruptEnable as u32;
+        self
+    }
+
+    /// Returns the number of bytes this block transfers.
+    pub fn len(&self) -> usize {
+        self.length as usize
+    }
+}
+
+/// Points each block in `blocks` at the one after it, ending the chain with
+/// the last.
+fn link(blocks: &mut [ControlBlock]) {
+    for i in 1..blocks.len() {
+        blocks[i - 1].next = bus_address(&blocks[i] as *const ControlBlock as usize);
+    }
+
+    if let Some(last) = blocks.last_mut() {
+        last.next = 0;
+    }
+}
+
+/// One bit per channel, set while a `Channel` for it exists.
+///
+/// Claims are plain loads and stores, as for GPIO pins: exclusive accesses
+/// don't work until the MMU and caches are enabled.
+static CLAIMED: AtomicU16 = AtomicU16::new(0);
+
+/// A DMA channel.
+pub struct Channel {
+    number: u8,
+    registers: &'static mut Registers,
+}
+
+impl Channel {
+    /// Claims, enables and resets channel `number`.
+    ///
+    /// Returns `None` if the channel isn't in `AVAILABLE` or is already
+    /// claimed.
+    pub fn take(number: u8) -> Option<Channel> {
+        let mask = 1u16.checked_shl(number as u32).unwrap_or(0);
+        let claimed = CLAIMED.load(Relaxed);
+        if AVAILABLE & mask == 0 || claimed & mask != 0 {
+            return None;
+        }
+        CLAIMED.store(claimed | mask, Relaxed);
+
+        let global = unsafe { &mut *(GLOBAL_BASE as *mut GlobalRegisters) };
+        global.ENABLE.or_mask(mask as u32);
+
+        let address = DMA_BASE + number as usize * 0x100;
+        let mut channel = Channel {
+            number,
+            registers: unsafe { &mut *(address as *mut Registers) },
+        };
+        channel.reset();
+        Some(channel)
+    }
+
+    /// Returns the channel's number.
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    /// Returns `true` if this is a lite channel, which transfers at most 64KiB
+    /// per control block.
+    pub fn is_lite(&self) -> bool {
+        self.number >= FIRST_LITE
+    }
+
+    /// Returns the interrupt this channel raises. Channels 11 to 14 share
+    /// theirs.
+    pub fn interrupt(&self) -> Interrupt {
+        match self.number {
+            0 => Interrupt::Dma0,
+            1 => Interrupt::Dma1,
+            2 => Interrupt::Dma2,
+            3 => Interrupt::Dma3,
+            4 => Interrupt::Dma4,
+            5 => Interrupt::Dma5,
+            6 => Interrupt::Dma6,
+            7 => Interrupt::Dma7,
+            8 => Interrupt::Dma8,
+            9 => Interrupt::Dma9,
+            10 => Interrupt::Dma10,
+            _ => Interrupt::DmaShared,
+        }
+    }
+
+    /// Returns `true` if a block with an interrupt completed since the
+    /// interrupt was last cleared.
+    pub fn is_interrupt_pending(&self) -> bool {
+        self.registers.CS.has_mask(Status::Interrupt as u32)
+    }
+
+    /// Clears the channel's interrupt.
+    pub fn clear_interrupt(&mut self) {
+        self.registers.CS.write(Status::Interrupt as u32);
+    }
+
+    /// Runs the chain of `blocks` and waits for it to finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TooLong` without starting if a block is too long for this
+    /// channel, and `Bus` if the engine reports an error.
+    pub fn run(&mut self, blocks: &mut [ControlBlock]) -> Result<(), Error> {
+        self.begin(blocks)?;
+        self.finish()
+    }
+
+    /// Starts the chain of `blocks` and returns without waiting for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TooLong` without starting if a block is too long for this
+    /// channel.
+    ///
+    /// # Safety
+    ///
+    /// The returned `Transfer` must be dropped or waited on, not leaked: the
+    /// engine keeps using `blocks` and their buffers until then.
+    pub unsafe fn start<'t, 'a: 't>(&'t mut self, blocks: &'t mut [ControlBlock<'a>])
+                                     -> Result<Transfer<'t>, Error> {
+        self.begin(blocks)?;
+        Ok(Transfer { channel: self })
+    }
+
+    /// Links `blocks` and points the channel at the first.
+    fn begin(&mut self, blocks: &mut [ControlBlock]) -> Result<(), Error> {
+        let max = if self.is_lite() { LITE_MAX_LENGTH } else { MAX_LENGTH };
+        if blocks.iter().any(|block| block.len() > max) {
+            return Err(Error::TooLong);
+        }
+
+        link(blocks);
+        let first = match blocks.first() {
+            Some(block) => bus_address(block as *const ControlBlock as usize),
+            None => return Ok(()),
+        };
+
+        // The blocks and buffers must be in memory before the engine reads them.
+        fence(Ordering::SeqCst);
+        self.registers.CS.write(Status::End as u32 | Status::Interrupt as u32);
+        self.registers.DEBUG.write(DEBUG_ERRORS);
+        self.registers.CONBLK_AD.write(first);
+        self.registers.CS.write(Status::Active as u32 | Status::Priorities as u32
+                                | Status::WaitForOutstandingWrites as u32);
+        Ok(())
+    }
+
+    /// Returns `true` while the channel is running a chain.
+    fn is_active(&self) -> bool {
+        self.registers.CS.has_mask(Status::Active as u32)
+    }
+
+    /// Waits for the running chain to finish and returns its outcome.
+    fn finish(&mut self) -> Result<(), Error> {
+        while self.is_active() {
+            if self.registers.CS.has_mask(Status::Error as u32) {
+                let errors = self.registers.DEBUG.read() & DEBUG_ERRORS;
+                self.reset();
+                return Err(Error::Bus(errors));
+            }
+        }
+
+        // Make the engine's writes visible before the buffers are read.
+        fence(Ordering::SeqCst);
+        match self.registers.DEBUG.read() & DEBUG_ERRORS {
+            0 => Ok(()),
+            errors => Err(Error::Bus(errors)),
+        }
+    }
+
+    /// Stops any running chain and resets the channel.
+    fn reset(&mut self) {
+        if self.is_active() {
+            // Pause, then abort the current block and let the engine finish
+            // its outstanding writes before resetting.
+            self.registers.CS.write(0);
+            self.registers.NEXTCONBK.write(0);
+            self.registers.CS.write(Status::Abort as u32);
+            for _ in 0..ABORT_SPINS {
+                if !self.is_active() {
+                    break;
+                }
+            }
+        }
+
+        self.registers.CS.write(Status::Reset as u32);
+        self.registers.CS.write(Status::End as u32 | Status::Interrupt as u32);
+        self.registers.DEBUG.write(DEBUG_ERRORS);
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.reset();
+        CLAIMED.store(CLAIMED.load(Relaxed) & !(1 << self.number), Relaxed);
+    }
+}
+
+/// A running chain of control blocks, started with `Channel::start()`.
+///
+/// Dropping an unfinished transfer aborts it.
+pub struct Transfer<'t> {
+    channel: &'t mut Channel,
+}
+
+impl<'t> Transfer<'t> {
+    /// Returns `true` once the chain has finished or stopped on an error.
+    pub fn is_done(&self) -> bool {
+        !self.channel.is_active() || self.channel.registers.CS.has_mask(Status::Error as u32)
+    }
+
+    /// Waits for the chain to finish and returns its outcome.
+    pub fn wait(self) -> Result<(), Error> {
+        self.channel.finish()
+    }
+}
+
+impl<'t> Drop for Transfer<'t> {
+    fn drop(&mut self) {
+        if self.channel.is_active() {
+            self.channel.reset();
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use core::mem;
+
+use common::{bus_address, IO_BUS_BASE};
+use super::{link, ControlBlock, Peripheral};
+
+/// `TI` bits the tests look for.
+const INTEN: u32 = 1 << 0;
+const WAIT_RESP: u32 = 1 << 3;
+const DEST_INC: u32 = 1 << 4;
+const DEST_DREQ: u32 = 1 << 6;
+const SRC_INC: u32 = 1 << 8;
+const SRC_DREQ: u32 = 1 << 10;
+
+fn address<T>(value: *const T) -> u32 {
+    bus_address(value as usize)
+}
+
+#[test]
+fn control_block_layout() {
+    assert_eq!(mem::size_of::<ControlBlock>(), 32);
+    assert_eq!(mem::align_of::<ControlBlock>(), 32);
+}
+
+#[test]
+fn copy() {
+    let src = [1u32, 2, 3];
+    let mut dst = [0u32; 3];
+    let dst_address = address(dst.as_ptr());
+    let block = ControlBlock::copy(&src, &mut dst);
+
+    assert_eq!(block.info, SRC_INC | DEST_INC | WAIT_RESP);
+    assert_eq!(block.source, address(src.as_ptr()));
+    assert_eq!(block.dest, dst_address);
+    assert_eq!(block.len(), 12);
+    assert_eq!(block.next, 0);
+}
+
+#[test]
+#[should_panic]
+fn copy_length_mismatch() {
+    let src = [0u32; 2];
+    let mut dst = [0u32; 3];
+    ControlBlock::copy(&src, &mut dst);
+}
+
+#[test]
+fn fill() {
+    let value = 0xDEADBEEF;
+    let mut dst = [0u32; 5];
+    let block = ControlBlock::fill(&value, &mut dst).with_interrupt();
+
+    assert_eq!(block.info, DEST_INC | WAIT_RESP | INTEN);
+    assert_eq!(block.source, address(&value));
+    assert_eq!(block.len(), 20);
+}
+
+#[test]
+fn peripherals() {
+    let words = [0u32; 4];
+    let block = ControlBlock::to_peripheral(&words, Peripheral::UartTx);
+    assert_eq!(block.info, SRC_INC | DEST_DREQ | WAIT_RESP | 12 << 16);
+    assert_eq!(block.dest, IO_BUS_BASE + 0x201000);
+
+    let mut words = [0u32; 4];
+    let block = ControlBlock::from_peripheral(Peripheral::Emmc, &mut words);
+    assert_eq!(block.info, DEST_INC | SRC_DREQ | WAIT_RESP | 11 << 16);
+    assert_eq!(block.source, IO_BUS_BASE + 0x300020);
+}
+
+#[test]
+fn chain() {
+    let (a, b) = ([1u32; 2], [2u32; 2]);
+    let (mut c, mut d) = ([0u32; 2], [0u32; 2]);
+    let mut blocks = [ControlBlock::copy(&a, &mut c), ControlBlock::copy(&b, &mut d)];
+    blocks[1].next = 0x1234;
+    link(&mut blocks);
+
+    assert_eq!(blocks[0].next, address(&blocks[1]));
+    assert_eq!(blocks[1].next, 0);
+    assert_eq!(address(&blocks[0]) % 32, 0);
+}
@@ -0,0 +1,571 @@
+use std::io;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+use block::{self, BlockDevice, SECTOR_SIZE};
+use common::IO_BASE;
+use gpio::{Alt, Gpio, Function};
+use mailbox::{self, Clock, GetClockRate};
+use timer;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the `EMMC` registers.
+const EMMC_BASE: usize = IO_BASE + 0x300000;
+
+/// SD clock rate during card identification, in Hz.
+const IDENTIFICATION_CLOCK: u32 = 400_000;
+
+/// SD clock rate once the card is identified, in Hz.
+const TRANSFER_CLOCK: u32 = 25_000_000;
+
+/// How long the controller or card may take to finish a command, in
+/// microseconds.
+const COMMAND_TIMEOUT: u64 = 100_000;
+
+/// How long the card may take to send or accept a block, in microseconds.
+const DATA_TIMEOUT: u64 = 500_000;
+
+/// How long the card may take to power up, in microseconds.
+const POWER_UP_TIMEOUT: u64 = 1_000_000;
+
+/// Most blocks one command can transfer: the block count is 16 bits wide.
+const MAX_BLOCKS: usize = 0xFFFF;
+
+/// `SEND_IF_COND` argument: 2.7-3.6V supply and the `0xAA` check pattern.
+const IF_COND: u32 = 0x1AA;
+
+/// OCR bits for a 2.7-3.6V supply.
+const OCR_VOLTAGES: u32 = 0x00FF8000;
+
+/// OCR bit the host sets if it supports high capacity cards, and the card
+/// sets if it is one.
+const OCR_HIGH_CAPACITY: u32 = 1 << 30;
+
+/// OCR bit the card sets once it has powered up.
+const OCR_POWERED_UP: u32 = 1 << 31;
+
+/// `SET_BUS_WIDTH` argument for a 4-bit bus.
+const BUS_WIDTH_4: u32 = 0b10;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    ARG2: Volatile<u32>,
+    BLKSIZECNT: Volatile<u32>,
+    ARG1: Volatile<u32>,
+    CMDTM: Volatile<u32>,
+    RESP: [ReadVolatile<u32>; 4],
+    DATA: Volatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONTROL0: Volatile<u32>,
+    CONTROL1: Volatile<u32>,
+    INTERRUPT: Volatile<u32>,
+    IRPT_MASK: Volatile<u32>,
+    IRPT_EN: Volatile<u32>,
+    CONTROL2: Volatile<u32>,
+    __r0: [Reserved<u32>; 4],
+    FORCE_IRPT: Volatile<u32>,
+    __r1: [Reserved<u32>; 7],
+    BOOT_TIMEOUT: Volatile<u32>,
+    DBG_SEL: Volatile<u32>,
+    __r2: [Reserved<u32>; 2],
+    EXRDFIFO_CFG: Volatile<u32>,
+    EXRDFIFO_EN: Volatile<u32>,
+    TUNE_STEP: Volatile<u32>,
+    TUNE_STEPS_STD: Volatile<u32>,
+    TUNE_STEPS_DDR: Volatile<u32>,
+    __r3: [Reserved<u32>; 23],
+    SPI_INT_SPT: Volatile<u32>,
+    __r4: [Reserved<u32>; 2],
+    SLOTISR_VER: ReadVolatile<u32>,
+}
+
+/// Bit fields of the `STATUS` register.
+#[repr(u32)]
+enum Status {
+    CommandInhibit = 1 << 0,
+    DataInhibit = 1 << 1,
+}
+
+/// Bit fields of the `CONTROL0` register.
+#[repr(u32)]
+enum Control0 {
+    BusWidth4 = 1 << 1,
+}
+
+/// Bit fields of the `CONTROL1` register.
+#[repr(u32)]
+enum Control1 {
+    ClockInternalEnable = 1 << 0,
+    ClockStable = 1 << 1,
+    ClockEnable = 1 << 2,
+    /// The longest data timeout, `base clock * 2^27`.
+    DataTimeoutMax = 0xE << 16,
+    ResetHost = 1 << 24,
+    ResetCommand = 1 << 25,
+    ResetData = 1 << 26,
+}
+
+/// The clock divisor fields of `CONTROL1`.
+const CONTROL1_DIVISOR: u32 = 0x3FF << 6;
+
+/// Bit fields of the `INTERRUPT` register.
+#[repr(u32)]
+enum Flag {
+    CommandDone = 1 << 0,
+    DataDone = 1 << 1,
+    WriteReady = 1 << 4,
+    ReadReady = 1 << 5,
+    Error = 1 << 15,
+    CommandTimeout = 1 << 16,
+    DataTimeout = 1 << 20,
+}
+
+/// The error bits of `INTERRUPT`.
+const INTERRUPT_ERRORS: u32 = 0xFFFF0000 | Flag::Error as u32;
+
+/// `CMDTM` values for the commands used, with the flags describing their
+/// response and data transfer.
+mod command {
+    const BLOCK_COUNT: u32 = 1 << 1;
+    const AUTO_CMD12: u32 = 1 << 2;
+    const READ: u32 = 1 << 4;
+    const MULTI_BLOCK: u32 = 1 << 5;
+    const RESPONSE_136: u32 = 1 << 16;
+    const RESPONSE_48: u32 = 2 << 16;
+    pub const RESPONSE_48_BUSY: u32 = 3 << 16;
+    pub const RESPONSE_MASK: u32 = 3 << 16;
+    const CRC_CHECK: u32 = 1 << 19;
+    const INDEX_CHECK: u32 = 1 << 20;
+    pub const DATA: u32 = 1 << 21;
+
+    /// A normal 48-bit response with CRC and command index.
+    const R1: u32 = RESPONSE_48 | CRC_CHECK | INDEX_CHECK;
+
+    pub const GO_IDLE_STATE: u32 = 0 << 24;
+    pub const ALL_SEND_CID: u32 = 2 << 24 | RESPONSE_136 | CRC_CHECK;
+    pub const SEND_RELATIVE_ADDR: u32 = 3 << 24 | R1;
+    pub const SELECT_CARD: u32 = 7 << 24 | RESPONSE_48_BUSY | CRC_CHECK | INDEX_CHECK;
+    pub const SEND_IF_COND: u32 = 8 << 24 | R1;
+    pub const SET_BLOCKLEN: u32 = 16 << 24 | R1;
+    pub const READ_SINGLE_BLOCK: u32 = 17 << 24 | R1 | DATA | READ;
+    pub const READ_MULTIPLE_BLOCK: u32 =
+        18 << 24 | R1 | DATA | READ | MULTI_BLOCK | BLOCK_COUNT | AUTO_CMD12;
+    pub const WRITE_BLOCK: u32 = 24 << 24 | R1 | DATA;
+    pub const WRITE_MULTIPLE_BLOCK: u32 =
+        25 << 24 | R1 | DATA | MULTI_BLOCK | BLOCK_COUNT | AUTO_CMD12;
+    pub const APP_CMD: u32 = 55 << 24 | R1;
+
+    /// Application commands, sent after `APP_CMD`.
+    pub const SET_BUS_WIDTH: u32 = 6 << 24 | R1;
+    /// The OCR response carries no CRC or command index.
+    pub const SD_SEND_OP_COND: u32 = 41 << 24 | RESPONSE_48;
+}
+
+/// Errors from the EMMC controller or the card.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The firmware didn't report the EMMC base clock.
+    Clock(mailbox::Error),
+    /// The controller or the card didn't respond in time.
+    Timeout,
+    /// Command `index` failed with the error bits `flags` of `INTERRUPT`.
+    Command { index: u8, flags: u32 },
+    /// The card doesn't work at our supply voltage.
+    UnsupportedCard,
+    /// The sector is beyond what the card can address.
+    OutOfRange,
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::Timeout => io::Error::new(io::ErrorKind::TimedOut, "SD card timed out"),
+            Error::OutOfRange => io::Error::new(io::ErrorKind::InvalidInput, "sector out of range"),
+            Error::Clock(_) => io::Error::new(io::ErrorKind::Other, "EMMC clock unknown"),
+            Error::Command { .. } => io::Error::new(io::ErrorKind::Other, "SD command failed"),
+            Error::UnsupportedCard => io::Error::new(io::ErrorKind::Other, "unsupported SD card"),
+        }
+    }
+}
+
+/// The Arasan SDHCI controller, `EMMC`, driving the SD card.
+///
+/// Transfers are polled through the data register one word at a time. Cards
+/// are run in 4-bit mode at 25MHz.
+pub struct Emmc {
+    registers: &'static mut Registers,
+    base_clock: u32,
+    rca: u32,
+    high_capacity: bool,
+    /// GPIO pins 48 to 53, returned when the driver is dropped.
+    _pins: Option<[Gpio<Alt>; 6]>,
+}
+
+impl Emmc {
+    /// Routes GPIO pins 48 to 53 to the controller, resets it and initializes
+    /// the inserted card.
+    ///
+    /// The firmware connects the SD card to its own `SDHOST` controller; this
+    /// takes the card over.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the controller's clock can't be determined or the
+    /// card can't be initialized, for instance because there is none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the GPIO pins are already in use. The pins are
+    /// returned when the `Emmc` is dropped, or on error.
+    pub fn new() -> Result<Emmc, Error> {
+        let pins = [48, 49, 50, 51, 52, 53].map(|pin| Gpio::new(pin).into_alt(Function::Alt3));
+
+        let base_clock = match mailbox::property(&GetClockRate(Clock::Emmc)) {
+            Ok(0) => return Err(Error::Clock(mailbox::Error::Failed)),
+            Ok(rate) => rate,
+            Err(error) => return Err(Error::Clock(error)),
+        };
+
+        let mut emmc = unsafe { Emmc::at(EMMC_BASE, base_clock) };
+        emmc._pins = Some(pins);
+        emmc.initialize()?;
+        Ok(emmc)
+    }
+
+    /// Returns a handle to EMMC registers at `base`, clocked at `base_clock`
+    /// Hz, without initializing the controller or a card. The handle
+    /// addresses a standard capacity card by byte until one is identified.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to a `'static` EMMC register block.
+    pub(crate) unsafe fn at(base: usize, base_clock: u32) -> Emmc {
+        Emmc {
+            registers: &mut *(base as *mut Registers),
+            base_clock,
+            rca: 0,
+            high_capacity: false,
+            _pins: None,
+        }
+    }
+
+    /// Returns `true` if the card is high or extended capacity (SDHC/SDXC)
+    /// and so addressed by sector rather than by byte.
+    pub fn is_high_capacity(&self) -> bool {
+        self.high_capacity
+    }
+
+    /// Returns the card's relative address.
+    pub fn rca(&self) -> u16 {
+        self.rca as u16
+    }
+
+    /// Reads the sectors starting at `start` into `buf`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` isn't a whole number of sectors.
+    pub fn read_blocks(&mut self, start: u64, buf: &mut [u8]) -> Result<(), Error> {
+        assert!(buf.len() % SECTOR_SIZE == 0, "Emmc::read_blocks(): partial sector");
+        for (i, chunk) in buf.chunks_mut(MAX_BLOCKS * SECTOR_SIZE).enumerate() {
+            let address = self.address(start + (i * MAX_BLOCKS) as u64)?;
+            let result = self.read_data(address, chunk);
+            if result.is_err() {
+                self.reset_lines();
+            }
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `buf` to the sectors starting at `start`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` isn't a whole number of sectors.
+    pub fn write_blocks(&mut self, start: u64, buf: &[u8]) -> Result<(), Error> {
+        assert!(buf.len() % SECTOR_SIZE == 0, "Emmc::write_blocks(): partial sector");
+        for (i, chunk) in buf.chunks(MAX_BLOCKS * SECTOR_SIZE).enumerate() {
+            let address = self.address(start + (i * MAX_BLOCKS) as u64)?;
+            let result = self.write_data(address, chunk);
+            if result.is_err() {
+                self.reset_lines();
+            }
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Resets the controller, then identifies and selects the card.
+    fn initialize(&mut self) -> Result<(), Error> {
+        self.reset()?;
+        self.command(command::GO_IDLE_STATE, 0, 0)?;
+
+        // Version 2 cards echo the check pattern if they accept the voltage.
+        // Version 1 cards don't respond at all.
+        let version2 = match self.command(command::SEND_IF_COND, IF_COND, 0) {
+            Ok(()) if self.registers.RESP[0].read() & 0xFFF == IF_COND => true,
+            Ok(()) => return Err(Error::UnsupportedCard),
+            Err(Error::Timeout) => false,
+            Err(error) => return Err(error),
+        };
+
+        let mut request = OCR_VOLTAGES;
+        if version2 {
+            request |= OCR_HIGH_CAPACITY;
+        }
+
+        let deadline = timer::current_time() + POWER_UP_TIMEOUT;
+        let ocr = loop {
+            self.app_command(command::SD_SEND_OP_COND, request)?;
+            let ocr = self.registers.RESP[0].read();
+            if ocr & OCR_POWERED_UP != 0 {
+                break ocr;
+            }
+
+            if timer::current_time() > deadline {
+                return Err(Error::Timeout);
+            }
+            timer::spin_sleep_ms(10);
+        };
+
+        if ocr & OCR_VOLTAGES == 0 {
+            return Err(Error::UnsupportedCard);
+        }
+        self.high_capacity = ocr & OCR_HIGH_CAPACITY != 0;
+
+        self.command(command::ALL_SEND_CID, 0, 0)?;
+        self.command(command::SEND_RELATIVE_ADDR, 0, 0)?;
+        self.rca = self.registers.RESP[0].read() >> 16;
+
+        self.set_clock(TRANSFER_CLOCK)?;
+        let rca = self.rca;
+        self.command(command::SELECT_CARD, rca << 16, 0)?;
+        if !self.high_capacity {
+            self.command(command::SET_BLOCKLEN, SECTOR_SIZE as u32, 0)?;
+        }
+
+        // Every SD memory card supports a 4-bit bus.
+        self.app_command(command::SET_BUS_WIDTH, BUS_WIDTH_4)?;
+        self.registers.CONTROL0.or_mask(Control0::BusWidth4 as u32);
+        Ok(())
+    }
+
+    /// Resets the whole controller and starts the identification clock.
+    fn reset(&mut self) -> Result<(), Error> {
+        self.registers.CONTROL0.write(0);
+        self.registers.CONTROL2.write(0);
+        self.registers.CONTROL1.write(Control1::ResetHost as u32);
+        self.wait(COMMAND_TIMEOUT, |r| r.CONTROL1.read() & Control1::ResetHost as u32 == 0)?;
+
+        self.registers.CONTROL1.write(Control1::ClockInternalEnable as u32
+                                      | Control1::DataTimeoutMax as u32);
+        self.set_clock(IDENTIFICATION_CLOCK)?;
+
+        // Completion and errors are polled, so flag everything but raise no
+        // interrupts.
+        self.registers.IRPT_EN.write(0);
+        self.registers.IRPT_MASK.write(!0);
+        self.registers.INTERRUPT.write(!0);
+        Ok(())
+    }
+
+    /// Resets the command and data lines after an error, so the next command
+    /// isn't inhibited.
+    fn reset_lines(&mut self) {
+        let lines = Control1::ResetCommand as u32 | Control1::ResetData as u32;
+        self.registers.CONTROL1.or_mask(lines);
+        let _ = self.wait(COMMAND_TIMEOUT, |r| r.CONTROL1.read() & lines == 0);
+        self.registers.INTERRUPT.write(!0);
+    }
+
+    /// Sets the SD clock to the fastest rate no higher than `hz`.
+    fn set_clock(&mut self, hz: u32) -> Result<(), Error> {
+        let inhibit = Status::CommandInhibit as u32 | Status::DataInhibit as u32;
+        self.wait(COMMAND_TIMEOUT, |r| r.STATUS.read() & inhibit == 0)?;
+        self.registers.CONTROL1.and_mask(!(Control1::ClockEnable as u32));
+        timer::spin_sleep_us(10);
+
+        // 10-bit divided clock mode: the SD clock is `base / (2 * divisor)`.
+        let divisor = (self.base_clock as u64).div_ceil(2 * hz as u64).min(0x3FF) as u32;
+        let fields = (divisor & 0xFF) << 8 | (divisor >> 8) << 6;
+        let control = self.registers.CONTROL1.read() & !CONTROL1_DIVISOR;
+        self.registers.CONTROL1.write(control | fields | Control1::ClockInternalEnable as u32);
+        self.wait(COMMAND_TIMEOUT, |r| r.CONTROL1.read() & Control1::ClockStable as u32 != 0)?;
+
+        self.registers.CONTROL1.or_mask(Control1::ClockEnable as u32);
+        timer::spin_sleep_us(10);
+        Ok(())
+    }
+
+    /// Spins until `done` returns `true` for the registers, for at most
+    /// `timeout` microseconds.
+    fn wait<F: Fn(&Registers) -> bool>(&self, timeout: u64, done: F) -> Result<(), Error> {
+        let deadline = timer::current_time() + timeout;
+        while !done(&*self.registers) {
+            if timer::current_time() > deadline {
+                return Err(Error::Timeout);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits for one of the `INTERRUPT` bits in `flags` or an error, then
+    /// clears `flags`.
+    fn wait_for_flag(&mut self, cmd: u32, flags: u32, timeout: u64) -> Result<(), Error> {
+        self.wait(timeout, |r| r.INTERRUPT.read() & (flags | INTERRUPT_ERRORS) != 0)?;
+
+        let interrupt = self.registers.INTERRUPT.read();
+        if interrupt & INTERRUPT_ERRORS != 0 {
+            self.reset_lines();
+            if interrupt & (Flag::CommandTimeout as u32 | Flag::DataTimeout as u32) != 0 {
+                return Err(Error::Timeout);
+            }
+            return Err(Error::Command { index: (cmd >> 24) as u8, flags: interrupt & INTERRUPT_ERRORS });
+        }
+
+        self.registers.INTERRUPT.write(flags);
+        Ok(())
+    }
+
+    /// Issues command `cmd` with argument `arg`, transferring `blocks` blocks
+    /// if it has data, and waits for its response.
+    fn command(&mut self, cmd: u32, arg: u32, blocks: u32) -> Result<(), Error> {
+        let busy = cmd & command::RESPONSE_MASK == command::RESPONSE_48_BUSY;
+        let mut inhibit = Status::CommandInhibit as u32;
+        if busy || cmd & command::DATA != 0 {
+            inhibit |= Status::DataInhibit as u32;
+        }
+        self.wait(COMMAND_TIMEOUT, |r| r.STATUS.read() & inhibit == 0)?;
+
+        self.issue(cmd, arg, blocks);
+        self.wait_for_flag(cmd, Flag::CommandDone as u32, COMMAND_TIMEOUT)?;
+
+        // The card holds the data line while it's busy.
+        if busy {
+            self.wait_for_flag(cmd, Flag::DataDone as u32, DATA_TIMEOUT)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the `INTERRUPT` flags and starts command `cmd` with argument
+    /// `arg` and `blocks` sector-sized blocks.
+    fn issue(&mut self, cmd: u32, arg: u32, blocks: u32) {
+        self.registers.INTERRUPT.write(!0);
+        self.registers.BLKSIZECNT.write(blocks << 16 | SECTOR_SIZE as u32);
+        self.registers.ARG1.write(arg);
+        self.registers.CMDTM.write(cmd);
+    }
+
+    /// Issues the application command `cmd` with argument `arg`.
+    fn app_command(&mut self, cmd: u32, arg: u32) -> Result<(), Error> {
+        let rca = self.rca;
+        self.command(command::APP_CMD, rca << 16, 0)?;
+        self.command(cmd, arg, 0)
+    }
+
+    /// Returns the command argument addressing `sector`.
+    fn address(&self, sector: u64) -> Result<u32, Error> {
+        let address = if self.high_capacity {
+            Some(sector)
+        } else {
+            sector.checked_mul(SECTOR_SIZE as u64)
+        };
+
+        match address {
+            Some(address) if address <= u32::MAX as u64 => Ok(address as u32),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+
+    /// Reads `buf.len() / SECTOR_SIZE` blocks starting at `address`.
+    fn read_data(&mut self, address: u32, buf: &mut [u8]) -> Result<(), Error> {
+        let blocks = buf.len() / SECTOR_SIZE;
+        let cmd = match blocks {
+            1 => command::READ_SINGLE_BLOCK,
+            _ => command::READ_MULTIPLE_BLOCK,
+        };
+
+        self.command(cmd, address, blocks as u32)?;
+        for block in buf.chunks_mut(SECTOR_SIZE) {
+            self.wait_for_flag(cmd, Flag::ReadReady as u32, DATA_TIMEOUT)?;
+            for word in block.chunks_mut(4) {
+                let value = self.registers.DATA.read();
+                word[0] = value as u8;
+                word[1] = (value >> 8) as u8;
+                word[2] = (value >> 16) as u8;
+                word[3] = (value >> 24) as u8;
+            }
+        }
+
+        self.wait_for_flag(cmd, Flag::DataDone as u32, DATA_TIMEOUT)
+    }
+
+    /// Writes `buf` to the `buf.len() / SECTOR_SIZE` blocks starting at
+    /// `address`.
+    fn write_data(&mut self, address: u32, buf: &[u8]) -> Result<(), Error> {
+        let blocks = buf.len() / SECTOR_SIZE;
+        let cmd = match blocks {
+            1 => command::WRITE_BLOCK,
+            _ => command::WRITE_MULTIPLE_BLOCK,
+        };
+
+        self.command(cmd, address, blocks as u32)?;
+        for block in buf.chunks(SECTOR_SIZE) {
+            self.wait_for_flag(cmd, Flag::WriteReady as u32, DATA_TIMEOUT)?;
+            for word in block.chunks(4) {
+                let value = word[0] as u32
+                    | (word[1] as u32) << 8
+                    | (word[2] as u32) << 16
+                    | (word[3] as u32) << 24;
+                self.registers.DATA.write(value);
+            }
+        }
+
+        self.wait_for_flag(cmd, Flag::DataDone as u32, DATA_TIMEOUT)
+    }
+}
+
+impl BlockDevice for Emmc {
+    fn read_sector(&mut self, n: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.len() >= SECTOR_SIZE {
+            self.read_blocks(n, &mut buf[..SECTOR_SIZE])?;
+            return Ok(SECTOR_SIZE);
+        }
+
+        let mut sector = [0u8; SECTOR_SIZE];
+        self.read_blocks(n, &mut sector)?;
+        buf.copy_from_slice(&sector[..buf.len()]);
+        Ok(buf.len())
+    }
+
+    fn write_sector(&mut self, n: u64, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() < SECTOR_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "buffer is shorter than a sector"));
+        }
+
+        self.write_blocks(n, &buf[..SECTOR_SIZE])?;
+        Ok(SECTOR_SIZE)
+    }
+
+    fn read_sectors(&mut self, start: u64, buf: &mut [u8]) -> io::Result<usize> {
+        block::check_length(buf.len(), SECTOR_SIZE)?;
+        self.read_blocks(start, buf)?;
+        Ok(buf.len())
+    }
+
+    fn write_sectors(&mut self, start: u64, buf: &[u8]) -> io::Result<usize> {
+        block::check_length(buf.len(), SECTOR_SIZE)?;
+        self.write_blocks(start, buf)?;
+        Ok(buf.len())
+    }
+}
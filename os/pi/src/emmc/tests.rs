@@ -0,0 +1,56 @@
+use block::SECTOR_SIZE;
+use test_util::MockRegs;
+
+use super::{command, Emmc, Error};
+
+/// Word offsets of the EMMC registers used below.
+const BLKSIZECNT: usize = 1;
+const ARG1: usize = 2;
+const CMDTM: usize = 3;
+const INTERRUPT: usize = 12;
+
+#[test]
+fn commands() {
+    // Index, response type, checks, data direction and multi-block flags, as
+    // listed in the BCM2835 documentation's `CMDTM` description.
+    assert_eq!(command::GO_IDLE_STATE, 0x0000_0000);
+    assert_eq!(command::ALL_SEND_CID, 0x0209_0000);
+    assert_eq!(command::SEND_IF_COND, 0x081A_0000);
+    assert_eq!(command::SELECT_CARD, 0x071B_0000);
+    assert_eq!(command::READ_SINGLE_BLOCK, 0x113A_0010);
+    assert_eq!(command::READ_MULTIPLE_BLOCK, 0x123A_0036);
+    assert_eq!(command::WRITE_BLOCK, 0x183A_0000);
+    assert_eq!(command::WRITE_MULTIPLE_BLOCK, 0x193A_0026);
+    assert_eq!(command::SD_SEND_OP_COND, 0x2902_0000);
+}
+
+#[test]
+fn addressing() {
+    let regs = MockRegs::<64>::new();
+    let mut emmc = unsafe { Emmc::at(regs.base(), 0) };
+
+    // Standard capacity cards are addressed by byte.
+    assert_eq!(emmc.address(3), Ok(3 * SECTOR_SIZE as u32));
+    let last = (u32::MAX as u64 + 1) / SECTOR_SIZE as u64 - 1;
+    assert_eq!(emmc.address(last), Ok(last as u32 * SECTOR_SIZE as u32));
+    assert_eq!(emmc.address(last + 1), Err(Error::OutOfRange));
+    assert_eq!(emmc.address(u64::MAX), Err(Error::OutOfRange));
+
+    // High capacity cards are addressed by sector.
+    emmc.high_capacity = true;
+    assert_eq!(emmc.address(3), Ok(3));
+    assert_eq!(emmc.address(u32::MAX as u64), Ok(u32::MAX));
+    assert_eq!(emmc.address(u32::MAX as u64 + 1), Err(Error::OutOfRange));
+}
+
+#[test]
+fn issue() {
+    let regs = MockRegs::<64>::new();
+    let mut emmc = unsafe { Emmc::at(regs.base(), 0) };
+
+    emmc.issue(command::READ_MULTIPLE_BLOCK, 0x1000, 8);
+    assert_eq!(regs[INTERRUPT], !0);
+    assert_eq!(regs[BLKSIZECNT], 8 << 16 | SECTOR_SIZE as u32);
+    assert_eq!(regs[ARG1], 0x1000);
+    assert_eq!(regs[CMDTM], command::READ_MULTIPLE_BLOCK);
+}
@@ -0,0 +1,578 @@
+use core::fmt;
+use core::str;
+
+#[cfg(test)]
+mod tests;
+
+/// The first word of every flattened device tree.
+pub const MAGIC: u32 = 0xD00DFEED;
+
+/// Size of the header in bytes.
+const HEADER_SIZE: usize = 40;
+
+/// Structure block tokens.
+const BEGIN_NODE: u32 = 1;
+const END_NODE: u32 = 2;
+const PROP: u32 = 3;
+const NOP: u32 = 4;
+const END: u32 = 9;
+
+/// How many levels below the root `Fdt::find_phandle()` searches. Real trees
+/// are a handful of levels deep; the cap bounds the stack the search uses on
+/// a malformed one.
+const MAX_DEPTH: usize = 32;
+
+/// `#address-cells` and `#size-cells` when a node doesn't set them.
+const DEFAULT_CELLS: Cells = Cells { address: 2, size: 1 };
+
+/// Errors from validating a device tree blob.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The blob doesn't start with `MAGIC`.
+    BadMagic,
+    /// The blob's layout isn't compatible with version 17.
+    UnsupportedVersion(u32),
+    /// The blob is shorter than its header claims, or a block lies outside
+    /// it.
+    Truncated,
+    /// The structure block is malformed.
+    BadStructure,
+}
+
+/// Reads the big-endian word at `offset` in `data`.
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads the big-endian double word at `offset` in `data`.
+fn be64(data: &[u8], offset: usize) -> Option<u64> {
+    Some((be32(data, offset)? as u64) << 32 | be32(data, offset + 4)? as u64)
+}
+
+/// Reads `count` cells starting at `data`, keeping the low 64 bits.
+fn read_cells(data: &[u8], count: u32) -> u64 {
+    (0..count as usize).fold(0, |value, i| value << 32 | be32(data, 4 * i).unwrap_or(0) as u64)
+}
+
+/// Returns the NUL-terminated string at the start of `data`.
+fn c_str(data: &[u8]) -> Option<&str> {
+    let len = data.iter().position(|&byte| byte == 0)?;
+    str::from_utf8(&data[..len]).ok()
+}
+
+/// Rounds `offset` up to the next token boundary.
+fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// A token of the structure block.
+enum Token<'a> {
+    BeginNode(&'a str),
+    EndNode,
+    Property(Property<'a>),
+    End,
+}
+
+/// The number of cells in addresses and sizes of a node's children.
+#[derive(Debug, Copy, Clone)]
+struct Cells {
+    address: u32,
+    size: u32,
+}
+
+/// A validated flattened device tree blob.
+///
+/// Nothing is copied out of the blob: nodes, properties and strings borrow
+/// from it.
+#[derive(Copy, Clone)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    reservations: &'a [u8],
+    structure: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> Fdt<'a> {
+    /// Validates the header and structure block of the device tree in `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `data` doesn't hold a well-formed device tree of
+    /// a version compatible with 17.
+    pub fn new(data: &'a [u8]) -> Result<Fdt<'a>, Error> {
+        if be32(data, 0) != Some(MAGIC) {
+            return Err(Error::BadMagic);
+        }
+
+        let field = |i: usize| be32(data, 4 * i).ok_or(Error::Truncated);
+        let total_size = field(1)? as usize;
+        let (version, last_compatible) = (field(5)?, field(6)?);
+        if version < 16 || last_compatible > 17 {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        if total_size < HEADER_SIZE || data.len() < total_size {
+            return Err(Error::Truncated);
+        }
+
+        let data = &data[..total_size];
+        let block = |offset: u32, size: Option<u32>| {
+            let start = offset as usize;
+            let end = match size {
+                Some(size) => start.checked_add(size as usize),
+                None => Some(total_size),
+            };
+            end.and_then(|end| data.get(start..end)).ok_or(Error::Truncated)
+        };
+
+        // Version 16 has no structure block size.
+        let structure_size = match version {
+            16 => None,
+            _ => Some(field(9)?),
+        };
+        let fdt = Fdt {
+            data,
+            reservations: block(field(4)?, None)?,
+            structure: block(field(2)?, structure_size)?,
+            strings: block(field(3)?, Some(field(8)?))?,
+        };
+
+        fdt.validate()?;
+        Ok(fdt)
+    }
+
+    /// Validates the device tree at `addr`. See `new()`.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to readable memory at least 8 bytes long and, if it
+    /// holds `MAGIC`, as long as the size in the header. The memory must not
+    /// be written to while the returned `Fdt` is in use.
+    pub unsafe fn from_addr(addr: usize) -> Result<Fdt<'static>, Error> {
+        let header = ::core::slice::from_raw_parts(addr as *const u8, 8);
+        if be32(header, 0) != Some(MAGIC) {
+            return Err(Error::BadMagic);
+        }
+
+        let total_size = be32(header, 4).unwrap_or(0) as usize;
+        Fdt::new(::core::slice::from_raw_parts(addr as *const u8, total_size.max(HEADER_SIZE)))
+    }
+
+    /// Returns the size of the blob in bytes.
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the entries of the memory reservation block: regions the
+    /// kernel must leave alone.
+    pub fn reservations(&self) -> Reservations<'a> {
+        Reservations { data: self.reservations, offset: 0 }
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> Node<'a> {
+        match self.token(0) {
+            Some((Token::BeginNode(name), offset)) => {
+                Node { fdt: *self, name, offset, parent_cells: DEFAULT_CELLS }
+            }
+            _ => unreachable!("Fdt: validated tree has no root node"),
+        }
+    }
+
+    /// Returns the node at the absolute `path`, such as `/soc/gpio@7e200000`.
+    /// A path component without a unit address also matches a node with
+    /// one, so `/memory` finds `/memory@0`.
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        if !path.starts_with('/') {
+            return None;
+        }
+
+        path.split('/')
+            .filter(|component| !component.is_empty())
+            .try_fold(self.root(), |node, component| node.child(component))
+    }
+
+    /// Returns the node whose `phandle` is `phandle`. Nodes more than
+    /// `MAX_DEPTH` levels below the root aren't searched.
+    pub fn find_phandle(&self, phandle: u32) -> Option<Node<'a>> {
+        fn find<'a>(node: Node<'a>, phandle: u32, depth: usize) -> Option<Node<'a>> {
+            if node.phandle() == Some(phandle) {
+                return Some(node);
+            }
+            if depth == MAX_DEPTH {
+                return None;
+            }
+
+            node.children().find_map(|child| find(child, phandle, depth + 1))
+        }
+
+        find(self.root(), phandle, 0)
+    }
+
+    /// Returns the `reg` ranges of the first memory node: the RAM available
+    /// to the ARM.
+    pub fn memory(&self) -> Option<Reg<'a>> {
+        self.root()
+            .children()
+            .find(|node| {
+                node.base_name() == "memory"
+                    || node.property("device_type").and_then(|p| p.as_str()) == Some("memory")
+            })
+            .and_then(|node| node.reg())
+    }
+
+    /// Returns the kernel command line from `/chosen`.
+    pub fn bootargs(&self) -> Option<&'a str> {
+        self.find_node("/chosen")?.property("bootargs")?.as_str()
+    }
+
+    /// Returns the location of the initial ramdisk from `/chosen`.
+    pub fn initrd(&self) -> Option<Region> {
+        let chosen = self.find_node("/chosen")?;
+        let start = chosen.property("linux,initrd-start")?.as_u64()?;
+        let end = chosen.property("linux,initrd-end")?.as_u64()?;
+        if end >= start {
+            Some(Region { address: start, size: end - start })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the token at `offset` in the structure block, skipping `NOP`s,
+    /// and the offset of the token after it. Returns `None` if the token is
+    /// malformed.
+    fn token(&self, mut offset: usize) -> Option<(Token<'a>, usize)> {
+        let structure = self.structure;
+        loop {
+            let token = be32(structure, offset)?;
+            offset += 4;
+            match token {
+                NOP => continue,
+                BEGIN_NODE => {
+                    let name = c_str(structure.get(offset..)?)?;
+                    return Some((Token::BeginNode(name), align(offset + name.len() + 1)));
+                }
+                END_NODE => return Some((Token::EndNode, offset)),
+                PROP => {
+                    let len = be32(structure, offset)? as usize;
+                    let name = c_str(self.strings.get(be32(structure, offset + 4)? as usize..)?)?;
+                    let start = offset + 8;
+                    let value = structure.get(start..start.checked_add(len)?)?;
+                    return Some((Token::Property(Property { name, value }), align(start + len)));
+                }
+                END => return Some((Token::End, offset)),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Returns the offset just past the `END_NODE` closing the node whose
+    /// contents start at `offset`.
+    fn skip_node(&self, mut offset: usize) -> Option<usize> {
+        let mut depth = 1;
+        loop {
+            let (token, next) = self.token(offset)?;
+            match token {
+                Token::BeginNode(_) => depth += 1,
+                Token::EndNode => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(next);
+                    }
+                }
+                Token::Property(_) => {}
+                Token::End => return None,
+            }
+            offset = next;
+        }
+    }
+
+    /// Checks that the structure block is a single root node, with
+    /// properties only inside nodes, followed by `END`.
+    fn validate(&self) -> Result<(), Error> {
+        let (mut offset, mut depth, mut seen_root) = (0, 0usize, false);
+        loop {
+            let (token, next) = self.token(offset).ok_or(Error::BadStructure)?;
+            match token {
+                Token::BeginNode(_) if depth == 0 && seen_root => return Err(Error::BadStructure),
+                Token::BeginNode(_) => {
+                    depth += 1;
+                    seen_root = true;
+                }
+                Token::EndNode if depth == 0 => return Err(Error::BadStructure),
+                Token::EndNode => depth -= 1,
+                Token::Property(_) if depth == 0 => return Err(Error::BadStructure),
+                Token::Property(_) => {}
+                Token::End if depth == 0 && seen_root => return Ok(()),
+                Token::End => return Err(Error::BadStructure),
+            }
+            offset = next;
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Fdt<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fdt").field("total_size", &self.total_size()).finish()
+    }
+}
+
+/// A node of a device tree.
+#[derive(Copy, Clone)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+    name: &'a str,
+    /// Offset of the first token after the node's `BEGIN_NODE`.
+    offset: usize,
+    /// The cells of the parent, which `reg` is laid out by.
+    parent_cells: Cells,
+}
+
+impl<'a> Node<'a> {
+    /// Returns the node's name, including its unit address. The root node's
+    /// name is empty.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the node's name without its unit address.
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap_or(self.name)
+    }
+
+    /// Returns the node's unit address, the part of its name after `@`.
+    pub fn unit_address(&self) -> Option<&'a str> {
+        self.name.split_once('@').map(|(_, address)| address)
+    }
+
+    /// Returns an iterator over the node's properties.
+    pub fn properties(&self) -> Properties<'a> {
+        Properties { fdt: self.fdt, offset: self.offset }
+    }
+
+    /// Returns the property called `name`.
+    pub fn property(&self, name: &str) -> Option<Property<'a>> {
+        self.properties().find(|property| property.name() == name)
+    }
+
+    /// Returns an iterator over the node's children.
+    pub fn children(&self) -> Children<'a> {
+        Children { fdt: self.fdt, offset: Some(self.offset), cells: self.cells() }
+    }
+
+    /// Returns the child called `name`. A `name` without a unit address also
+    /// matches a child with one.
+    pub fn child(&self, name: &str) -> Option<Node<'a>> {
+        let has_unit_address = name.contains('@');
+        self.children().find(|child| {
+            child.name() == name || (!has_unit_address && child.base_name() == name)
+        })
+    }
+
+    /// Returns the number of cells in the addresses of the node's children.
+    pub fn address_cells(&self) -> u32 {
+        self.cells().address
+    }
+
+    /// Returns the number of cells in the sizes of the node's children.
+    pub fn size_cells(&self) -> u32 {
+        self.cells().size
+    }
+
+    /// Returns the node's `phandle`.
+    pub fn phandle(&self) -> Option<u32> {
+        self.property("phandle")
+            .or_else(|| self.property("linux,phandle"))
+            .and_then(|property| property.as_u32())
+    }
+
+    /// Returns the address ranges in the node's `reg` property, laid out by
+    /// its parent's `#address-cells` and `#size-cells`.
+    pub fn reg(&self) -> Option<Reg<'a>> {
+        let value = self.property("reg")?.value();
+        Some(Reg { value, cells: self.parent_cells })
+    }
+
+    fn cells(&self) -> Cells {
+        let cells = |name, default| {
+            self.property(name).and_then(|property| property.as_u32()).unwrap_or(default)
+        };
+
+        Cells {
+            address: cells("#address-cells", DEFAULT_CELLS.address),
+            size: cells("#size-cells", DEFAULT_CELLS.size),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Node<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node").field("name", &self.name).finish()
+    }
+}
+
+/// A property of a device tree node.
+#[derive(Debug, Copy, Clone)]
+pub struct Property<'a> {
+    name: &'a str,
+    value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    /// Returns the property's name.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the property's raw value.
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Returns the value as a single cell.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.value.len() {
+            4 => be32(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a one or two cell number.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => be32(self.value, 0).map(|value| value as u64),
+            8 => be64(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a string, or the first string of a string list.
+    pub fn as_str(&self) -> Option<&'a str> {
+        c_str(self.value)
+    }
+
+    /// Returns an iterator over the strings of a string list, such as
+    /// `compatible`. Stops at the first string that isn't valid UTF-8.
+    pub fn strings(&self) -> Strings<'a> {
+        Strings { value: self.value }
+    }
+}
+
+/// An iterator over the strings of a string list property.
+#[derive(Debug)]
+pub struct Strings<'a> {
+    value: &'a [u8],
+}
+
+impl<'a> Iterator for Strings<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let string = c_str(self.value)?;
+        self.value = &self.value[string.len() + 1..];
+        Some(string)
+    }
+}
+
+/// An iterator over the properties of a node.
+pub struct Properties<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+}
+
+impl<'a> Iterator for Properties<'a> {
+    type Item = Property<'a>;
+
+    fn next(&mut self) -> Option<Property<'a>> {
+        match self.fdt.token(self.offset)? {
+            (Token::Property(property), next) => {
+                self.offset = next;
+                Some(property)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An iterator over the children of a node.
+pub struct Children<'a> {
+    fdt: Fdt<'a>,
+    /// Where to look for the next child, or `None` once they ran out.
+    offset: Option<usize>,
+    /// The cells of the parent node.
+    cells: Cells,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        let mut offset = self.offset?;
+        self.offset = None;
+        loop {
+            match self.fdt.token(offset)? {
+                (Token::Property(_), next) => offset = next,
+                (Token::BeginNode(name), next) => {
+                    self.offset = self.fdt.skip_node(next);
+                    return Some(Node { fdt: self.fdt, name, offset: next, parent_cells: self.cells });
+                }
+                (Token::EndNode, _) | (Token::End, _) => return None,
+            }
+        }
+    }
+}
+
+/// A range of physical addresses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub address: u64,
+    pub size: u64,
+}
+
+/// An iterator over the address ranges in a `reg` property.
+#[derive(Debug)]
+pub struct Reg<'a> {
+    value: &'a [u8],
+    cells: Cells,
+}
+
+impl<'a> Iterator for Reg<'a> {
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Region> {
+        let address_len = 4 * self.cells.address as usize;
+        let len = address_len + 4 * self.cells.size as usize;
+        if len == 0 || self.value.len() < len {
+            return None;
+        }
+
+        let region = Region {
+            address: read_cells(self.value, self.cells.address),
+            size: read_cells(&self.value[address_len..], self.cells.size),
+        };
+        self.value = &self.value[len..];
+        Some(region)
+    }
+}
+
+/// An iterator over the entries of the memory reservation block.
+#[derive(Debug)]
+pub struct Reservations<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Reservations<'a> {
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Region> {
+        let address = be64(self.data, self.offset)?;
+        let size = be64(self.data, self.offset + 8)?;
+        if address == 0 && size == 0 {
+            return None;
+        }
+
+        self.offset += 16;
+        Some(Region { address, size })
+    }
+}
@@ -0,0 +1,240 @@
+use std::vec::Vec;
+
+use super::{Error, Fdt, Region, BEGIN_NODE, END, END_NODE, MAGIC, MAX_DEPTH, NOP, PROP};
+
+/// Builds a device tree blob.
+struct Builder {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    reservations: Vec<(u64, u64)>,
+}
+
+impl Builder {
+    fn new() -> Builder {
+        Builder { structure: Vec::new(), strings: Vec::new(), reservations: Vec::new() }
+    }
+
+    fn word(&mut self, word: u32) -> &mut Builder {
+        self.structure.extend_from_slice(&word.to_be_bytes());
+        self
+    }
+
+    fn pad(&mut self) {
+        while self.structure.len() % 4 != 0 {
+            self.structure.push(0);
+        }
+    }
+
+    fn begin(&mut self, name: &str) -> &mut Builder {
+        self.word(BEGIN_NODE);
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        self.pad();
+        self
+    }
+
+    fn end(&mut self) -> &mut Builder {
+        self.word(END_NODE)
+    }
+
+    fn prop(&mut self, name: &str, value: &[u8]) -> &mut Builder {
+        let offset = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+
+        self.word(PROP).word(value.len() as u32).word(offset);
+        self.structure.extend_from_slice(value);
+        self.pad();
+        self
+    }
+
+    fn prop_cells(&mut self, name: &str, cells: &[u32]) -> &mut Builder {
+        let value: Vec<u8> = cells.iter().flat_map(|cell| cell.to_be_bytes().to_vec()).collect();
+        self.prop(name, &value)
+    }
+
+    fn reserve(&mut self, address: u64, size: u64) -> &mut Builder {
+        self.reservations.push((address, size));
+        self
+    }
+
+    fn build(&mut self) -> Vec<u8> {
+        self.word(END);
+
+        let reservations = 40;
+        let structure = reservations + 16 * (self.reservations.len() + 1);
+        let strings = structure + self.structure.len();
+        let total = strings + self.strings.len();
+
+        let mut blob = Vec::new();
+        let header = [MAGIC, total as u32, structure as u32, strings as u32, reservations as u32,
+                      17, 16, 0, self.strings.len() as u32, self.structure.len() as u32];
+        for word in header.iter() {
+            blob.extend_from_slice(&word.to_be_bytes());
+        }
+        for &(address, size) in self.reservations.iter().chain([(0, 0)].iter()) {
+            blob.extend_from_slice(&address.to_be_bytes());
+            blob.extend_from_slice(&size.to_be_bytes());
+        }
+        blob.extend_from_slice(&self.structure);
+        blob.extend_from_slice(&self.strings);
+        blob
+    }
+}
+
+/// A tree shaped like the one the firmware passes on a Pi 3.
+fn pi3() -> Vec<u8> {
+    Builder::new()
+        .reserve(0, 0x1000)
+        .begin("")
+            .prop_cells("#address-cells", &[1])
+            .prop_cells("#size-cells", &[1])
+            .prop("compatible", b"raspberrypi,3-model-b\0brcm,bcm2837\0")
+            .begin("chosen")
+                .prop("bootargs", b"console=ttyS0 quiet\0")
+                .prop_cells("linux,initrd-start", &[0x2000000])
+                .prop_cells("linux,initrd-end", &[0x2400000])
+            .end()
+            .begin("soc")
+                .word(NOP)
+                .begin("gpio@7e200000")
+                    .prop_cells("phandle", &[0x10])
+                .end()
+            .end()
+            .begin("memory@0")
+                .prop("device_type", b"memory\0")
+                .prop_cells("reg", &[0, 0x3B400000])
+            .end()
+        .end()
+        .build()
+}
+
+#[test]
+fn header() {
+    let blob = pi3();
+    let fdt = Fdt::new(&blob).expect("valid tree");
+    assert_eq!(fdt.total_size(), blob.len());
+
+    let mut bad = blob.clone();
+    bad[0] = 0;
+    assert_eq!(Fdt::new(&bad).unwrap_err(), Error::BadMagic);
+    assert_eq!(Fdt::new(&blob[..blob.len() - 1]).unwrap_err(), Error::Truncated);
+
+    let mut old = blob.clone();
+    old[20..24].copy_from_slice(&1u32.to_be_bytes());
+    assert_eq!(Fdt::new(&old).unwrap_err(), Error::UnsupportedVersion(1));
+}
+
+#[test]
+fn malformed_structure() {
+    // An unterminated root node.
+    let blob = Builder::new().begin("").build();
+    assert_eq!(Fdt::new(&blob).unwrap_err(), Error::BadStructure);
+
+    // A property outside the root node.
+    let blob = Builder::new().prop("a", b"").begin("").end().build();
+    assert_eq!(Fdt::new(&blob).unwrap_err(), Error::BadStructure);
+}
+
+#[test]
+fn nodes_and_properties() {
+    let blob = pi3();
+    let fdt = Fdt::new(&blob).unwrap();
+    let root = fdt.root();
+    assert_eq!(root.name(), "");
+    assert_eq!((root.address_cells(), root.size_cells()), (1, 1));
+
+    let names: Vec<&str> = root.children().map(|node| node.name()).collect();
+    assert_eq!(names, ["chosen", "soc", "memory@0"]);
+
+    let compatible: Vec<&str> = root.property("compatible").unwrap().strings().collect();
+    assert_eq!(compatible, ["raspberrypi,3-model-b", "brcm,bcm2837"]);
+    assert!(root.property("missing").is_none());
+}
+
+#[test]
+fn paths() {
+    let blob = pi3();
+    let fdt = Fdt::new(&blob).unwrap();
+    assert_eq!(fdt.find_node("/").unwrap().name(), "");
+    assert_eq!(fdt.find_node("/soc/gpio").unwrap().unit_address(), Some("7e200000"));
+    assert_eq!(fdt.find_node("/soc/gpio@7e200000").unwrap().name(), "gpio@7e200000");
+    assert!(fdt.find_node("/soc/gpio@0").is_none());
+    assert!(fdt.find_node("soc").is_none());
+}
+
+#[test]
+fn phandles() {
+    let blob = pi3();
+    let fdt = Fdt::new(&blob).unwrap();
+    assert_eq!(fdt.find_phandle(0x10).unwrap().name(), "gpio@7e200000");
+    assert!(fdt.find_phandle(0x11).is_none());
+}
+
+#[test]
+fn deep_phandles() {
+    // Nests a node with phandle `depth` at every depth, one level deeper than
+    // `find_phandle()` searches.
+    let mut builder = Builder::new();
+    builder.begin("");
+    for depth in 1..=MAX_DEPTH + 1 {
+        builder.begin("node").prop_cells("phandle", &[depth as u32]);
+    }
+    for _ in 0..=MAX_DEPTH + 1 {
+        builder.end();
+    }
+    let blob = builder.build();
+
+    let fdt = Fdt::new(&blob).unwrap();
+    assert!(fdt.find_phandle(MAX_DEPTH as u32).is_some());
+    assert!(fdt.find_phandle(MAX_DEPTH as u32 + 1).is_none());
+}
+
+#[test]
+fn memory_and_chosen() {
+    let blob = pi3();
+    let fdt = Fdt::new(&blob).unwrap();
+    let memory: Vec<Region> = fdt.memory().unwrap().collect();
+    assert_eq!(memory, [Region { address: 0, size: 0x3B400000 }]);
+
+    assert_eq!(fdt.bootargs(), Some("console=ttyS0 quiet"));
+    assert_eq!(fdt.initrd(), Some(Region { address: 0x2000000, size: 0x400000 }));
+
+    let reserved: Vec<Region> = fdt.reservations().collect();
+    assert_eq!(reserved, [Region { address: 0, size: 0x1000 }]);
+}
+
+#[test]
+fn wide_cells() {
+    let blob = Builder::new()
+        .begin("")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .begin("memory")
+                .prop_cells("reg", &[0x1, 0x0, 0x0, 0x8000_0000, 0x0, 0x0, 0x0, 0x1000])
+            .end()
+        .end()
+        .build();
+
+    let fdt = Fdt::new(&blob).unwrap();
+    let memory: Vec<Region> = fdt.memory().unwrap().collect();
+    assert_eq!(memory, [
+        Region { address: 0x1_0000_0000, size: 0x8000_0000 },
+        Region { address: 0, size: 0x1000 },
+    ]);
+}
+
+#[test]
+fn default_cells() {
+    let blob = Builder::new()
+        .begin("")
+            .begin("memory")
+                .prop_cells("reg", &[0x1, 0x0, 0x1000])
+            .end()
+        .end()
+        .build();
+
+    let fdt = Fdt::new(&blob).unwrap();
+    let memory: Vec<Region> = fdt.memory().unwrap().collect();
+    assert_eq!(memory, [Region { address: 0x1_0000_0000, size: 0x1000 }]);
+}
@@ -0,0 +1,192 @@
+//! A 32-bit framebuffer allocated through the mailbox, drawing primitives and
+//! a text console rendered with a built-in font.
+
+use core::slice;
+
+use common::arm_address;
+use mailbox::{self, Mailbox, PropertyBuffer, Size};
+use mailbox::{SetPhysicalSize, SetVirtualSize, SetDepth, SetPixelOrder, AllocateBuffer, GetPitch};
+
+pub mod font;
+mod text;
+#[cfg(test)]
+mod tests;
+
+pub use self::text::TextConsole;
+
+/// A pixel value, `0x00RRGGBB`.
+pub type Color = u32;
+
+/// Returns the pixel value for the given red, green and blue components.
+pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+/// A rectangle of pixels in memory, stored row by row with `stride` pixels
+/// from the start of one row to the start of the next.
+///
+/// All drawing is clipped to the surface, so coordinates may lie outside it.
+pub struct Surface<'a> {
+    pixels: &'a mut [Color],
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+impl<'a> Surface<'a> {
+    /// Returns a `width` by `height` surface over `pixels`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than `width` or `pixels` is too short to
+    /// hold `height` rows.
+    pub fn new(pixels: &'a mut [Color], width: usize, height: usize, stride: usize) -> Surface<'a> {
+        if stride < width || (height > 0 && pixels.len() < (height - 1) * stride + width) {
+            panic!("Surface::new(): {} pixels can't hold {}x{} with stride {}",
+                   pixels.len(), width, height, stride);
+        }
+
+        Surface { pixels, width, height, stride }
+    }
+
+    /// Returns the width of the surface in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the surface in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the pixel at (`x`, `y`), or `None` if it's outside the surface.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some(self.pixels[y * self.stride + x])
+    }
+
+    /// Sets the pixel at (`x`, `y`) to `color`.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.stride + x] = color;
+        }
+    }
+
+    /// Fills the `width` by `height` rectangle at (`x`, `y`) with `color`.
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
+        let x_end = x.saturating_add(width).min(self.width);
+        let y_end = y.saturating_add(height).min(self.height);
+        for row in y.min(y_end)..y_end {
+            let start = row * self.stride;
+            for pixel in &mut self.pixels[start + x.min(x_end)..start + x_end] {
+                *pixel = color;
+            }
+        }
+    }
+
+    /// Fills the whole surface with `color`.
+    pub fn clear(&mut self, color: Color) {
+        let (width, height) = (self.width, self.height);
+        self.fill_rect(0, 0, width, height, color);
+    }
+
+    /// Copies all of `src` to (`x`, `y`).
+    pub fn blit(&mut self, x: usize, y: usize, src: &Surface) {
+        let width = src.width.min(self.width.saturating_sub(x));
+        let height = src.height.min(self.height.saturating_sub(y));
+        for row in 0..height {
+            let from = row * src.stride;
+            let to = (y + row) * self.stride + x;
+            self.pixels[to..to + width].copy_from_slice(&src.pixels[from..from + width]);
+        }
+    }
+
+    /// Moves the contents of the surface up by `rows` rows, filling the rows
+    /// uncovered at the bottom with `color`.
+    pub fn scroll_up(&mut self, rows: usize, color: Color) {
+        let rows = rows.min(self.height);
+        for row in rows..self.height {
+            let from = row * self.stride;
+            let to = (row - rows) * self.stride;
+            self.pixels.copy_within(from..from + self.width, to);
+        }
+
+        let (width, height) = (self.width, self.height);
+        self.fill_rect(0, height - rows, width, rows, color);
+    }
+}
+
+/// A 32-bit framebuffer shared with the GPU and shown on the display.
+#[derive(Debug)]
+pub struct Framebuffer {
+    base: usize,
+    size: usize,
+    width: usize,
+    height: usize,
+    pitch: usize,
+}
+
+impl Framebuffer {
+    /// Asks the firmware for a `width` by `height` 32-bit framebuffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the firmware rejects any of the requests, and
+    /// `Failed` if it doesn't provide a 32-bit buffer.
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, mailbox::Error> {
+        let size = Size { width, height };
+        let mut buffer = PropertyBuffer::new();
+        let physical = buffer.push(&SetPhysicalSize(size))?;
+        buffer.push(&SetVirtualSize(size))?;
+        let depth = buffer.push(&SetDepth(32))?;
+        buffer.push(&SetPixelOrder(0))?;
+        let allocation = buffer.push(&AllocateBuffer(16))?;
+        let pitch = buffer.push(&GetPitch)?;
+        buffer.send(&mut Mailbox::new())?;
+
+        let size = buffer.get(&physical)?;
+        let memory = buffer.get(&allocation)?;
+        if buffer.get(&depth)? != 32 || memory.base == 0 {
+            return Err(mailbox::Error::Failed);
+        }
+
+        Ok(Framebuffer {
+            base: arm_address(memory.base),
+            size: memory.size as usize,
+            width: size.width as usize,
+            height: size.height as usize,
+            pitch: buffer.get(&pitch)? as usize,
+        })
+    }
+
+    /// Returns the width of the framebuffer in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the framebuffer in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of bytes from the start of one row to the next.
+    pub fn pitch(&self) -> usize {
+        self.pitch
+    }
+
+    /// Returns a surface drawing to the framebuffer.
+    pub fn surface(&mut self) -> Surface<'_> {
+        let pixels = unsafe { slice::from_raw_parts_mut(self.base as *mut Color, self.size / 4) };
+        Surface::new(pixels, self.width, self.height, self.pitch / 4)
+    }
+
+    /// Consumes the framebuffer, returning a surface drawing to it that lives
+    /// for the rest of the program.
+    pub fn into_surface(self) -> Surface<'static> {
+        let pixels = unsafe { slice::from_raw_parts_mut(self.base as *mut Color, self.size / 4) };
+        Surface::new(pixels, self.width, self.height, self.pitch / 4)
+    }
+}
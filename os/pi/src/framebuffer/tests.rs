@@ -0,0 +1,97 @@
+use core::fmt::Write;
+
+use super::{font, rgb, Surface, TextConsole};
+
+const WHITE: u32 = 0x00FFFFFF;
+const BLUE: u32 = 0x000000FF;
+
+/// Returns whether the character cell at (`column`, `row`) of `surface`
+/// shows the glyph for `byte` in `fg` on `bg`.
+fn shows(surface: &Surface, column: usize, row: usize, byte: u8, fg: u32, bg: u32) -> bool {
+    let (x, y) = (column * font::WIDTH, row * font::HEIGHT);
+    font::glyph(byte).iter().enumerate().all(|(dy, bits)| {
+        (0..font::WIDTH).all(|dx| {
+            let expected = if bits & (0x80 >> dx) != 0 { fg } else { bg };
+            surface.pixel(x + dx, y + dy) == Some(expected)
+        })
+    })
+}
+
+#[test]
+fn rgb_packs_components() {
+    assert_eq!(rgb(0x12, 0x34, 0x56), 0x00123456);
+}
+
+#[test]
+fn fill_rect_clips() {
+    let mut pixels = [0u32; 8 * 4];
+    let mut surface = Surface::new(&mut pixels, 6, 4, 8);
+    surface.fill_rect(4, 2, 10, 10, 7);
+
+    for y in 0..4 {
+        for x in 0..6 {
+            let expected = if x >= 4 && y >= 2 { 7 } else { 0 };
+            assert_eq!(surface.pixel(x, y), Some(expected), "({}, {})", x, y);
+        }
+    }
+
+    // Padding past the width is never touched.
+    assert_eq!(surface.pixel(6, 3), None);
+    drop(surface);
+    assert_eq!(pixels[3 * 8 + 6], 0);
+}
+
+#[test]
+fn blit_and_scroll() {
+    let mut src_pixels = [0u32; 2 * 2];
+    let mut src = Surface::new(&mut src_pixels, 2, 2, 2);
+    src.set_pixel(0, 0, 1);
+    src.set_pixel(1, 0, 2);
+    src.set_pixel(0, 1, 3);
+    src.set_pixel(1, 1, 4);
+
+    let mut pixels = [0u32; 4 * 3];
+    let mut surface = Surface::new(&mut pixels, 4, 3, 4);
+    surface.blit(3, 1, &src);
+    assert_eq!(surface.pixel(3, 1), Some(1));
+    assert_eq!(surface.pixel(3, 2), Some(3));
+
+    surface.scroll_up(1, 9);
+    assert_eq!(surface.pixel(3, 0), Some(1));
+    assert_eq!(surface.pixel(3, 1), Some(3));
+    assert_eq!(surface.pixel(0, 2), Some(9));
+    assert_eq!(surface.pixel(3, 2), Some(9));
+}
+
+#[test]
+fn renders_text() {
+    let (width, height) = (4 * font::WIDTH, 2 * font::HEIGHT);
+    let mut pixels = [0u32; 4 * 8 * 2 * 13];
+    let mut console = TextConsole::new(Surface::new(&mut pixels, width, height, width), WHITE, BLUE);
+    assert_eq!((console.columns(), console.rows()), (4, 2));
+
+    write!(console, "Hi\n\x01").unwrap();
+    assert_eq!(console.cursor(), (1, 1));
+
+    let surface = console.surface();
+    assert!(shows(surface, 0, 0, b'H', WHITE, BLUE));
+    assert!(shows(surface, 1, 0, b'i', WHITE, BLUE));
+    assert!(shows(surface, 2, 0, b' ', WHITE, BLUE));
+    assert!(shows(surface, 0, 1, b'?', WHITE, BLUE));
+}
+
+#[test]
+fn wraps_and_scrolls() {
+    let (width, height) = (3 * font::WIDTH, 2 * font::HEIGHT);
+    let mut pixels = [0u32; 3 * 8 * 2 * 13];
+    let mut console = TextConsole::new(Surface::new(&mut pixels, width, height, width), WHITE, BLUE);
+
+    console.write_str("abcdefg").unwrap();
+    assert_eq!(console.cursor(), (1, 1));
+
+    let surface = console.surface();
+    assert!(shows(surface, 0, 0, b'd', WHITE, BLUE));
+    assert!(shows(surface, 2, 0, b'f', WHITE, BLUE));
+    assert!(shows(surface, 0, 1, b'g', WHITE, BLUE));
+    assert!(shows(surface, 1, 1, b' ', WHITE, BLUE));
+}
@@ -0,0 +1,124 @@
+use core::fmt;
+
+use super::{font, Color, Surface};
+
+/// Width of a tab stop in columns.
+const TAB_WIDTH: usize = 8;
+
+/// A scrolling text console drawn onto a `Surface` with the built-in font.
+///
+/// Handles `\n`, `\r`, `\t` and backspace (`0x08`). Bytes without a glyph are
+/// drawn as `?`.
+pub struct TextConsole<'a> {
+    surface: Surface<'a>,
+    column: usize,
+    row: usize,
+    columns: usize,
+    rows: usize,
+    foreground: Color,
+    background: Color,
+}
+
+impl<'a> TextConsole<'a> {
+    /// Returns a console covering `surface`, clearing it to `background`.
+    pub fn new(mut surface: Surface<'a>, foreground: Color, background: Color) -> TextConsole<'a> {
+        surface.clear(background);
+        TextConsole {
+            columns: surface.width() / font::WIDTH,
+            rows: surface.height() / font::HEIGHT,
+            surface,
+            column: 0,
+            row: 0,
+            foreground,
+            background,
+        }
+    }
+
+    /// Returns the number of character columns.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Returns the number of character rows.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the cursor position as `(column, row)`.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.column, self.row)
+    }
+
+    /// Sets the colors used for subsequent text.
+    pub fn set_colors(&mut self, foreground: Color, background: Color) {
+        self.foreground = foreground;
+        self.background = background;
+    }
+
+    /// Returns the underlying surface.
+    pub fn surface(&mut self) -> &mut Surface<'a> {
+        &mut self.surface
+    }
+
+    /// Draws the glyph for `byte` at character cell (`column`, `row`).
+    fn draw(&mut self, byte: u8, column: usize, row: usize) {
+        let (x, y) = (column * font::WIDTH, row * font::HEIGHT);
+        for (dy, bits) in font::glyph(byte).iter().enumerate() {
+            for dx in 0..font::WIDTH {
+                let color = match bits & (0x80 >> dx) {
+                    0 => self.background,
+                    _ => self.foreground,
+                };
+                self.surface.set_pixel(x + dx, y + dy, color);
+            }
+        }
+    }
+
+    /// Moves the cursor to the start of the next line, scrolling if needed.
+    fn newline(&mut self) {
+        self.column = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            let background = self.background;
+            self.surface.scroll_up(font::HEIGHT, background);
+        }
+    }
+
+    /// Writes `byte` at the cursor and advances it.
+    pub fn write_byte(&mut self, byte: u8) {
+        if self.columns == 0 || self.rows == 0 {
+            return;
+        }
+
+        match byte {
+            b'\n' => self.newline(),
+            b'\r' => self.column = 0,
+            b'\t' => {
+                let next = (self.column / TAB_WIDTH + 1) * TAB_WIDTH;
+                while self.column < next.min(self.columns) {
+                    self.write_byte(b' ');
+                }
+            }
+            0x08 => self.column = self.column.saturating_sub(1),
+            _ => {
+                if self.column >= self.columns {
+                    self.newline();
+                }
+
+                let (column, row) = (self.column, self.row);
+                self.draw(byte, column, row);
+                self.column += 1;
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Write for TextConsole<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
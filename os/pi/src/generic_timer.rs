@@ -0,0 +1,190 @@
+#[cfg(target_arch = "aarch64")]
+use core::arch::asm;
+
+use common::LOCAL_BASE;
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+/// `CNTx_CTL_EL0` bit enabling the timer.
+const CTL_ENABLE: u64 = 1 << 0;
+
+/// `CNTx_CTL_EL0` bit masking the timer's interrupt.
+const CTL_IMASK: u64 = 1 << 1;
+
+/// `CNTx_CTL_EL0` bit set when the timer condition is met.
+const CTL_ISTATUS: u64 = 1 << 2;
+
+/// Rate of the crystal the counter runs from, in Hz, used when the firmware
+/// left `CNTFRQ_EL0` unprogrammed.
+const DEFAULT_FREQUENCY: u64 = 19_200_000;
+
+/// Reads the system register `$reg`. Other architectures, such as the host
+/// running unit tests, read `0`.
+#[cfg(target_arch = "aarch64")]
+macro_rules! read_sysreg {
+    ($reg:literal) => {{
+        let value: u64;
+        unsafe { asm!(concat!("isb\n", "mrs {}, ", $reg), out(reg) value, options(nostack)) };
+        value
+    }};
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+macro_rules! read_sysreg {
+    ($reg:literal) => { 0u64 };
+}
+
+/// Writes `$value` to the system register `$reg`. Other architectures
+/// discard the write.
+#[cfg(target_arch = "aarch64")]
+macro_rules! write_sysreg {
+    ($reg:literal, $value:expr) => {
+        unsafe { asm!(concat!("msr ", $reg, ", {}\n", "isb"), in(reg) $value, options(nostack)) }
+    };
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+macro_rules! write_sysreg {
+    ($reg:literal, $value:expr) => {{ let _: u64 = $value; }};
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct LocalRegisters {
+    CONTROL: Volatile<u32>,
+    __r0: Reserved<u32>,
+    PRESCALER: Volatile<u32>,
+    __r1: [Reserved<u32>; 13],
+    TIMER_INT_CONTROL: [Volatile<u32>; 4],
+    MAILBOX_INT_CONTROL: [Volatile<u32>; 4],
+    IRQ_SOURCE: [ReadVolatile<u32>; 4],
+    FIQ_SOURCE: [ReadVolatile<u32>; 4],
+}
+
+/// One of the per-core ARMv8 generic timers available at EL1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// The non-secure EL1 physical timer, `CNTP`.
+    Physical,
+    /// The virtual timer, `CNTV`.
+    Virtual,
+}
+
+impl Kind {
+    /// Returns the bit of this timer's IRQ in the local timer interrupt
+    /// control and IRQ source registers.
+    fn irq_mask(self) -> u32 {
+        match self {
+            Kind::Physical => 1 << 1,
+            Kind::Virtual => 1 << 3,
+        }
+    }
+}
+
+/// Returns the number of the core executing this function.
+pub fn core_id() -> usize {
+    (read_sysreg!("mpidr_el1") & 0b11) as usize
+}
+
+/// Returns the frequency of the generic timer counter, in Hz. If the firmware
+/// didn't program `CNTFRQ_EL0`, returns the 19.2MHz the counter runs at on
+/// the Pi 3.
+pub fn frequency() -> u64 {
+    match read_sysreg!("cntfrq_el0") {
+        0 => DEFAULT_FREQUENCY,
+        freq => freq,
+    }
+}
+
+/// The calling core's ARMv8 generic timer.
+///
+/// Unlike the system timer in `pi::timer`, each core has its own generic
+/// timer, and its interrupt is delivered only to that core through the local
+/// interrupt controller.
+pub struct GenericTimer {
+    kind: Kind,
+    core: usize,
+    registers: &'static mut LocalRegisters,
+}
+
+impl GenericTimer {
+    /// Returns the generic timer `kind` of the calling core.
+    pub fn new(kind: Kind) -> GenericTimer {
+        GenericTimer {
+            kind: kind,
+            core: core_id(),
+            registers: unsafe { &mut *(LOCAL_BASE as *mut LocalRegisters) },
+        }
+    }
+
+    /// Returns the number of counter ticks since boot.
+    pub fn read(&self) -> u64 {
+        match self.kind {
+            Kind::Physical => read_sysreg!("cntpct_el0"),
+            Kind::Virtual => read_sysreg!("cntvct_el0"),
+        }
+    }
+
+    /// Returns the number of microseconds since boot.
+    pub fn read_us(&self) -> u64 {
+        (self.read() as u128 * 1_000_000 / frequency() as u128) as u64
+    }
+
+    fn read_ctl(&self) -> u64 {
+        match self.kind {
+            Kind::Physical => read_sysreg!("cntp_ctl_el0"),
+            Kind::Virtual => read_sysreg!("cntv_ctl_el0"),
+        }
+    }
+
+    fn write_ctl(&mut self, ctl: u64) {
+        match self.kind {
+            Kind::Physical => write_sysreg!("cntp_ctl_el0", ctl),
+            Kind::Virtual => write_sysreg!("cntv_ctl_el0", ctl),
+        }
+    }
+
+    /// Arms the timer to fire `ticks` counter ticks from now, clearing any
+    /// previous firing.
+    pub fn tick_in_ticks(&mut self, ticks: u32) {
+        match self.kind {
+            Kind::Physical => write_sysreg!("cntp_tval_el0", ticks as u64),
+            Kind::Virtual => write_sysreg!("cntv_tval_el0", ticks as u64),
+        }
+        self.write_ctl(CTL_ENABLE);
+    }
+
+    /// Arms the timer to fire `us` microseconds from now, clearing any
+    /// previous firing.
+    pub fn tick_in(&mut self, us: u32) {
+        let ticks = (us as u64 * frequency() / 1_000_000).min(u32::MAX as u64);
+        self.tick_in_ticks(ticks as u32);
+    }
+
+    /// Returns `true` if the timer has fired since it was last armed.
+    pub fn is_fired(&self) -> bool {
+        self.read_ctl() & CTL_ISTATUS != 0
+    }
+
+    /// Stops the timer. A pending interrupt is deasserted.
+    pub fn stop(&mut self) {
+        self.write_ctl(CTL_IMASK);
+    }
+
+    /// Routes this timer's interrupt to the calling core as an IRQ.
+    pub fn enable_interrupt(&mut self) {
+        let mask = self.kind.irq_mask();
+        self.registers.TIMER_INT_CONTROL[self.core].or_mask(mask);
+    }
+
+    /// Stops routing this timer's interrupt to the calling core.
+    pub fn disable_interrupt(&mut self) {
+        let mask = self.kind.irq_mask();
+        self.registers.TIMER_INT_CONTROL[self.core].and_mask(!mask);
+    }
+
+    /// Returns `true` if this timer's IRQ is pending at the calling core.
+    pub fn is_pending(&self) -> bool {
+        self.registers.IRQ_SOURCE[self.core].has_mask(self.kind.irq_mask())
+    }
+}
@@ -1,6 +1,11 @@
 use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicU32};
+use core::sync::atomic::Ordering::Relaxed;
 
 use common::{IO_BASE, states};
+use interrupt::Interrupt;
+use timer;
 use volatile::prelude::*;
 use volatile::{Volatile, WriteVolatile, ReadVolatile, Reserved};
 
@@ -17,6 +22,32 @@ pub enum Function {
     Alt5 = 0b010
 }
 
+/// The state of a pin's pull-up/pull-down resistor.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pull {
+    Off = 0b00,
+    Down = 0b01,
+    Up = 0b10,
+}
+
+/// A condition that sets a pin's event detect status bit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A synchronously sampled low-to-high transition.
+    RisingEdge,
+    /// A synchronously sampled high-to-low transition.
+    FallingEdge,
+    /// The pin is high.
+    HighLevel,
+    /// The pin is low.
+    LowLevel,
+    /// An unsampled low-to-high transition; catches very short pulses.
+    AsyncRisingEdge,
+    /// An unsampled high-to-low transition; catches very short pulses.
+    AsyncFallingEdge,
+}
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -58,51 +89,145 @@ states! {
 /// structure starts in the `Uninitialized` state and must be transitions into
 /// one of `Input`, `Output`, or `Alt` via the `into_input`, `into_output`, and
 /// `into_alt` methods before it can be used.
+///
+/// Pins are handed out by `GpioBank::pin()`, each at most once. Dropping a
+/// `Gpio` resets the pin to an input and returns it to the pool.
 pub struct Gpio<State> {
     pin: u8,
-    registers: &'static mut Registers,
+    /// The register block shared by every pin and the `GpioBank`. It is only
+    /// borrowed for the duration of a single operation on this pin.
+    registers: *mut Registers,
     _state: PhantomData<State>
 }
 
+// A `Gpio` only ever touches its own pin's bits.
+unsafe impl<State> Send for Gpio<State> {}
+
 /// The base address of the `GPIO` registers.
 const GPIO_BASE: usize = IO_BASE + 0x200000;
 
+/// One bit per pin, set while a `Gpio` for that pin exists.
+///
+/// Claims are plain loads and stores: exclusive accesses don't work until the
+/// MMU and caches are enabled, and only one core touches GPIO.
+static CLAIMED: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+/// Marks `pin` as claimed. Returns `false` if it already was.
+fn claim(pin: u8) -> bool {
+    let (bank, mask) = ((pin / 32) as usize, 1 << (pin % 32));
+    let claimed = CLAIMED[bank].load(Relaxed);
+    if claimed & mask != 0 {
+        return false;
+    }
+
+    CLAIMED[bank].store(claimed | mask, Relaxed);
+    true
+}
+
+/// Marks `pin` as free.
+fn unclaim(pin: u8) {
+    let (bank, mask) = ((pin / 32) as usize, 1 << (pin % 32));
+    CLAIMED[bank].store(CLAIMED[bank].load(Relaxed) & !mask, Relaxed);
+}
+
+impl Registers {
+    /// Returns the detect enable registers for `event`.
+    fn event_enable(&mut self, event: Event) -> &mut [Volatile<u32>; 2] {
+        match event {
+            Event::RisingEdge => &mut self.REN,
+            Event::FallingEdge => &mut self.FEN,
+            Event::HighLevel => &mut self.HEN,
+            Event::LowLevel => &mut self.LEN,
+            Event::AsyncRisingEdge => &mut self.AREN,
+            Event::AsyncFallingEdge => &mut self.AFEN,
+        }
+    }
+}
+
 impl<T> Gpio<T> {
     /// Transitions `self` to state `S`, consuming `self` and returning a new
     /// `Gpio` instance in state `S`. This method should _never_ be exposed to
     /// the public!
     #[inline(always)]
     fn transition<S>(self) -> Gpio<S> {
-        Gpio {
+        let gpio = Gpio {
             pin: self.pin,
             registers: self.registers,
             _state: PhantomData
-        }
+        };
+        // The pin stays claimed by the new `Gpio`.
+        mem::forget(self);
+        gpio
+    }
+
+    fn registers(&self) -> &Registers {
+        unsafe { &*self.registers }
+    }
+
+    fn registers_mut(&mut self) -> &mut Registers {
+        unsafe { &mut *self.registers }
+    }
+
+    /// Returns this pin's number.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+}
+
+impl<T> Drop for Gpio<T> {
+    /// Resets the pin to an input and returns it to the pool.
+    fn drop(&mut self) {
+        let pin = self.pin;
+        self.registers_mut().FSEL[(pin / 10) as usize].and_mask(!(0b111 << (3 * (pin % 10))));
+        unclaim(pin);
     }
 }
 
 impl Gpio<Uninitialized> {
-    /// Returns a new `GPIO` structure for pin number `pin`.
+    /// Claims pin number `pin` for a driver in this crate.
     ///
     /// # Panics
     ///
-    /// Panics if `pin` > `53`.
-    pub fn new(pin: u8) -> Gpio<Uninitialized> {
-        if pin > 53 {
-            panic!("Gpio::new(): pin {} exceeds maximum of 53", pin);
+    /// Panics if `pin` > `53` or if `pin` is already claimed. See `try_new()`.
+    pub(crate) fn new(pin: u8) -> Gpio<Uninitialized> {
+        match Gpio::try_new(pin) {
+            Some(gpio) => gpio,
+            None if pin > 53 => panic!("Gpio::new(): pin {} exceeds maximum of 53", pin),
+            None => panic!("Gpio::new(): pin {} is already in use", pin),
+        }
+    }
+
+    /// Claims pin number `pin` for a driver in this crate. Returns `None` if
+    /// `pin` > `53` or if a `Gpio` for `pin` exists.
+    pub(crate) fn try_new(pin: u8) -> Option<Gpio<Uninitialized>> {
+        unsafe { Gpio::at(pin, GPIO_BASE) }
+    }
+
+    /// Like `try_new()`, but with the GPIO registers at `base`. Pin claims
+    /// are global, so `pin` is claimed whichever block `base` points to.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to a `'static` GPIO register block.
+    pub(crate) unsafe fn at(pin: u8, base: usize) -> Option<Gpio<Uninitialized>> {
+        if pin > 53 || !claim(pin) {
+            return None;
         }
 
-        Gpio {
-            registers: unsafe { &mut *(GPIO_BASE as *mut Registers) },
+        Some(Gpio {
+            registers: base as *mut Registers,
             pin: pin,
             _state: PhantomData
-        }
+        })
     }
 
     /// Enables the alternative function `function` for `self`. Consumes self
     /// and returns a `Gpio` structure in the `Alt` state.
-    pub fn into_alt(self, function: Function) -> Gpio<Alt> {
-        self.registers.FSEL[(self.pin / 10) as usize].or_mask((function as u32) << (3 * (self.pin % 10)));
+    pub fn into_alt(mut self, function: Function) -> Gpio<Alt> {
+        let (index, shift) = ((self.pin / 10) as usize, 3 * (self.pin % 10));
+        let fsel = &mut self.registers_mut().FSEL[index];
+        let value = (fsel.read() & !(0b111 << shift)) | ((function as u32) << shift);
+        fsel.write(value);
         return self.transition();
     }
 
@@ -122,19 +247,209 @@ impl Gpio<Uninitialized> {
 impl Gpio<Output> {
     /// Sets (turns on) the pin.
     pub fn set(&mut self) {
-        self.registers.SET[(self.pin / 32) as usize].write(1 << (self.pin % 32))
+        let pin = self.pin;
+        self.registers_mut().SET[(pin / 32) as usize].write(1 << (pin % 32))
     }
 
     /// Clears (turns off) the pin.
     pub fn clear(&mut self) {
-        self.registers.CLR[(self.pin / 32) as usize].write(1 << (self.pin % 32))
+        let pin = self.pin;
+        self.registers_mut().CLR[(pin / 32) as usize].write(1 << (pin % 32))
+    }
+}
+
+/// The GPIO peripheral as a whole: the source of `Gpio` pins and of
+/// operations on several pins at once.
+///
+/// There is at most one `GpioBank`. Setting or clearing several pins through
+/// it takes a single register write per 32-pin bank, so pins 0-31 (and pins
+/// 32-53) change at exactly the same time.
+pub struct GpioBank {
+    registers: *mut Registers,
+}
+
+// The bank only writes the write-only `SET` and `CLR` registers and reads
+// `LEV`, none of which pins modify.
+unsafe impl Send for GpioBank {}
+
+/// Whether the `GpioBank` has been taken.
+static BANK_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl GpioBank {
+    /// Returns the `GpioBank` the first time it is called and `None` after.
+    pub fn take() -> Option<GpioBank> {
+        if BANK_TAKEN.load(Relaxed) {
+            return None;
+        }
+
+        BANK_TAKEN.store(true, Relaxed);
+        Some(GpioBank { registers: GPIO_BASE as *mut Registers })
+    }
+
+    /// Claims pin number `pin`. Returns `None` if `pin` > `53` or if the pin
+    /// is in use, by another `Gpio` or by a driver in this crate.
+    pub fn pin(&mut self, pin: u8) -> Option<Gpio<Uninitialized>> {
+        unsafe { Gpio::at(pin, self.registers as usize) }
+    }
+
+    fn registers(&self) -> &Registers {
+        unsafe { &*self.registers }
+    }
+
+    fn registers_mut(&mut self) -> &mut Registers {
+        unsafe { &mut *self.registers }
+    }
+
+    /// Returns the bits for `pins` in the `SET`/`CLR` registers.
+    fn masks(pins: &[&Gpio<Output>]) -> [u32; 2] {
+        let mut masks = [0; 2];
+        for gpio in pins {
+            masks[(gpio.pin / 32) as usize] |= 1 << (gpio.pin % 32);
+        }
+        masks
+    }
+
+    /// Sets (turns on) all of `pins` at once.
+    pub fn set(&mut self, pins: &[&Gpio<Output>]) {
+        for (i, &mask) in GpioBank::masks(pins).iter().enumerate() {
+            if mask != 0 {
+                self.registers_mut().SET[i].write(mask);
+            }
+        }
+    }
+
+    /// Clears (turns off) all of `pins` at once.
+    pub fn clear(&mut self, pins: &[&Gpio<Output>]) {
+        for (i, &mask) in GpioBank::masks(pins).iter().enumerate() {
+            if mask != 0 {
+                self.registers_mut().CLR[i].write(mask);
+            }
+        }
+    }
+
+    /// Sets `high`, then clears `low`.
+    pub fn write(&mut self, high: &[&Gpio<Output>], low: &[&Gpio<Output>]) {
+        self.set(high);
+        self.clear(low);
+    }
+
+    /// Reads the levels of all 54 pins. Bit `n` is set if pin `n` is high.
+    pub fn levels(&self) -> u64 {
+        let low = self.registers().LEV[0].read() as u64;
+        let high = self.registers().LEV[1].read() as u64;
+        (high << 32 | low) & ((1 << 54) - 1)
     }
 }
 
 impl Gpio<Input> {
     /// Reads the pin's value. Returns `true` if the level is high and `false`
     /// if the level is low.
-    pub fn level(&mut self) -> bool {
-        self.registers.LEV[(self.pin / 32) as usize].has_mask(1 << (self.pin % 32))
+    pub fn level(&self) -> bool {
+        self.registers().LEV[(self.pin / 32) as usize].has_mask(1 << (self.pin % 32))
+    }
+}
+
+impl Gpio<Input> {
+    /// Sets the pin's pull resistor to `pull`.
+    ///
+    /// The new state is clocked into the pin following the sequence on page
+    /// 101 of the BCM2837 documentation, which needs 150 cycles of set-up and
+    /// hold time. The system timer only counts whole microseconds, so a
+    /// 1-microsecond sleep can end almost at once; this method sleeps for 2
+    /// before clocking the pull in and again before releasing the clock.
+    pub fn set_pull(&mut self, pull: Pull) {
+        let (bank, mask) = ((self.pin / 32) as usize, 1 << (self.pin % 32));
+        let registers = self.registers_mut();
+        registers.PUD.write(pull as u32);
+        timer::spin_sleep_us(2);
+        registers.PUDCLK[bank].write(mask);
+        timer::spin_sleep_us(2);
+        registers.PUD.write(0);
+        registers.PUDCLK[bank].write(0);
+    }
+
+    /// Enables detection of `event` on this pin. Several events may be
+    /// enabled at once; any of them sets the pin's event status.
+    pub fn enable_event(&mut self, event: Event) {
+        let (bank, mask) = ((self.pin / 32) as usize, 1 << (self.pin % 32));
+        self.registers_mut().event_enable(event)[bank].or_mask(mask);
+    }
+
+    /// Disables detection of `event` on this pin.
+    pub fn disable_event(&mut self, event: Event) {
+        let (bank, mask) = ((self.pin / 32) as usize, 1 << (self.pin % 32));
+        self.registers_mut().event_enable(event)[bank].and_mask(!mask);
+    }
+
+    /// Returns `true` if an enabled event has been detected on this pin since
+    /// its status was last cleared.
+    pub fn has_event(&self) -> bool {
+        self.registers().EDS[(self.pin / 32) as usize].has_mask(1 << (self.pin % 32))
+    }
+
+    /// Clears this pin's event status. For level events the status is set
+    /// again immediately while the level persists.
+    pub fn clear_event(&mut self) {
+        // `EDS` is write-one-to-clear.
+        let pin = self.pin;
+        self.registers_mut().EDS[(pin / 32) as usize].write(1 << (pin % 32))
+    }
+
+    /// Returns the interrupt controller source raised by events on this pin.
+    /// Pins 0-27, 28-45 and 46-53 each share one of the three bank interrupts.
+    pub fn interrupt(&self) -> Interrupt {
+        match self.pin {
+            0..=27 => Interrupt::Gpio0,
+            28..=45 => Interrupt::Gpio1,
+            _ => Interrupt::Gpio2,
+        }
+    }
+}
+
+/// An input pin whose events are debounced in software.
+///
+/// An event is reported at most once per `interval`: further events within
+/// `interval` microseconds of the last reported one, such as the bounces of a
+/// mechanical button, are cleared and ignored.
+pub struct Debounced {
+    pin: Gpio<Input>,
+    interval: u64,
+    last: Option<u64>,
+}
+
+impl Debounced {
+    /// Debounces the events enabled on `pin` with an interval of `interval`
+    /// microseconds. Any pending event is cleared.
+    pub fn new(mut pin: Gpio<Input>, interval: u64) -> Debounced {
+        pin.clear_event();
+        Debounced { pin, interval, last: None }
+    }
+
+    /// Returns `true` if a debounced event occurred since the last call.
+    /// Suitable for calling from the pin's interrupt handler or in a loop.
+    pub fn poll(&mut self) -> bool {
+        if !self.pin.has_event() {
+            return false;
+        }
+
+        self.pin.clear_event();
+        let now = timer::current_time();
+        match self.last {
+            Some(last) if now - last < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Returns the underlying pin.
+    pub fn pin(&mut self) -> &mut Gpio<Input> {
+        &mut self.pin
+    }
+
+    /// Consumes `self`, returning the underlying pin.
+    pub fn into_inner(self) -> Gpio<Input> {
+        self.pin
     }
 }
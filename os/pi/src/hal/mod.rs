@@ -0,0 +1,116 @@
+//! Implementations of the `embedded-hal` traits for this crate's peripherals,
+//! so that drivers written against `embedded-hal` can run on the Pi. Enabled
+//! by the `hal` feature.
+
+use core::convert::Infallible;
+
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::blocking::serial::write::Default as BlockingWriteDefault;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial;
+
+use gpio::{Gpio, Input, Output};
+use timer::Timer;
+use uart::MiniUart;
+
+#[cfg(test)]
+mod tests;
+
+impl OutputPin for Gpio<Output> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.clear();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.set();
+        Ok(())
+    }
+}
+
+impl InputPin for Gpio<Input> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(self.level())
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(!self.level())
+    }
+}
+
+impl DelayUs<u32> for Timer {
+    fn delay_us(&mut self, us: u32) {
+        let start = self.read();
+        while self.read() - start < us as u64 { }
+    }
+}
+
+impl DelayUs<u16> for Timer {
+    fn delay_us(&mut self, us: u16) {
+        DelayUs::<u32>::delay_us(self, us as u32)
+    }
+}
+
+impl DelayUs<u8> for Timer {
+    fn delay_us(&mut self, us: u8) {
+        DelayUs::<u32>::delay_us(self, us as u32)
+    }
+}
+
+impl DelayMs<u32> for Timer {
+    fn delay_ms(&mut self, ms: u32) {
+        let start = self.read();
+        while self.read() - start < ms as u64 * 1000 { }
+    }
+}
+
+impl DelayMs<u16> for Timer {
+    fn delay_ms(&mut self, ms: u16) {
+        DelayMs::<u32>::delay_ms(self, ms as u32)
+    }
+}
+
+impl DelayMs<u8> for Timer {
+    fn delay_ms(&mut self, ms: u8) {
+        DelayMs::<u32>::delay_ms(self, ms as u32)
+    }
+}
+
+impl serial::Read<u8> for MiniUart {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        if !self.has_byte() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        Ok(self.read_byte())
+    }
+}
+
+impl serial::Write<u8> for MiniUart {
+    type Error = Infallible;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        if !self.can_write() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.write_byte(byte);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        if !self.is_idle() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        Ok(())
+    }
+}
+
+impl BlockingWriteDefault<u8> for MiniUart {}
@@ -0,0 +1,83 @@
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::{Read, Write};
+
+use gpio::Gpio;
+use test_util::MockRegs;
+use uart::MiniUart;
+
+// Pin claims are global, so each test claims different GPIO pins.
+
+/// Word offsets of the GPIO registers used below.
+const FSEL0: usize = 0;
+const SET0: usize = 7;
+const SET1: usize = 8;
+const CLR0: usize = 10;
+const LEV0: usize = 13;
+
+/// Word offsets of the mini UART registers used below.
+const MU_IO: usize = 0;
+const MU_LSR: usize = 5;
+
+#[test]
+fn output_pin() {
+    let regs = MockRegs::<48>::new();
+    let mut pin = unsafe { Gpio::at(3, regs.base()) }.unwrap().into_output();
+    assert_eq!(regs[FSEL0], 0b001 << 9);
+
+    assert_eq!(pin.set_high(), Ok::<(), Infallible>(()));
+    assert_eq!(regs[SET0], 1 << 3);
+    assert_eq!(regs[SET1], 0);
+
+    assert_eq!(pin.set_low(), Ok::<(), Infallible>(()));
+    assert_eq!(regs[CLR0], 1 << 3);
+}
+
+#[test]
+fn input_pin() {
+    let mut regs = MockRegs::<48>::new();
+    regs[FSEL0] = 0b111 << 15;
+    let pin = unsafe { Gpio::at(5, regs.base()) }.unwrap().into_input();
+    assert_eq!(regs[FSEL0], 0);
+
+    assert_eq!(pin.is_high(), Ok(false));
+    assert_eq!(pin.is_low(), Ok(true));
+
+    regs[LEV0] = 1 << 5;
+    assert_eq!(pin.is_high(), Ok(true));
+    assert_eq!(pin.is_low(), Ok(false));
+
+    // Claimed pins can't be claimed twice.
+    assert!(unsafe { Gpio::at(5, regs.base()) }.is_none());
+
+    // Dropped pins are reset to inputs and can be claimed again.
+    regs[FSEL0] = 0b001 << 15 | 0b001;
+    drop(pin);
+    assert_eq!(regs[FSEL0], 0b001);
+    assert!(unsafe { Gpio::at(5, regs.base()) }.is_some());
+}
+
+#[test]
+fn serial_read() {
+    let mut regs = MockRegs::<16>::new();
+    let mut uart = unsafe { MiniUart::at(regs.base()) };
+    assert_eq!(uart.read(), Err(nb::Error::WouldBlock));
+
+    regs[MU_IO] = b'x' as u32;
+    regs[MU_LSR] = 1;
+    assert_eq!(uart.read(), Ok(b'x'));
+}
+
+#[test]
+fn serial_write() {
+    let mut regs = MockRegs::<16>::new();
+    let mut uart = unsafe { MiniUart::at(regs.base()) };
+    assert_eq!(uart.write(b'y'), Err(nb::Error::WouldBlock));
+    assert_eq!(Write::flush(&mut uart), Err(nb::Error::WouldBlock));
+
+    regs[MU_LSR] = (1 << 5) | (1 << 6);
+    assert_eq!(uart.write(b'y'), Ok(()));
+    assert_eq!(regs[MU_IO], b'y' as u32);
+    assert_eq!(Write::flush(&mut uart), Ok(()));
+}
@@ -0,0 +1,326 @@
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use common::IO_BASE;
+use gpio::{Alt, Gpio, Function};
+use timer;
+use uart::DEFAULT_CORE_CLOCK;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the `BSC1` registers.
+const BSC1_REG_BASE: usize = IO_BASE + 0x804000;
+
+/// Depth of the controller's FIFO, in bytes.
+const FIFO_DEPTH: usize = 16;
+
+/// How long a transfer may go without progress before it's abandoned, in
+/// microseconds. Covers a stuck bus, which the clock-stretch timeout doesn't.
+const PROGRESS_TIMEOUT: u64 = 50_000;
+
+/// Bit fields of the `C` register.
+#[repr(u32)]
+enum Control {
+    Read = 1 << 0,
+    Clear = 0b11 << 4,
+    Start = 1 << 7,
+    Enable = 1 << 15,
+}
+
+/// Bit fields of the `S` register.
+#[repr(u32)]
+enum Status {
+    TransferActive = 1 << 0,
+    Done = 1 << 1,
+    TxSpace = 1 << 4,
+    RxData = 1 << 5,
+    AckError = 1 << 8,
+    ClockTimeout = 1 << 9,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    C: Volatile<u32>,
+    S: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    A: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    DIV: Volatile<u32>,
+    DEL: Volatile<u32>,
+    CLKT: Volatile<u32>,
+}
+
+/// Errors from an I2C transaction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The slave didn't acknowledge its address or a byte.
+    Nack,
+    /// The slave stretched the clock for longer than the configured timeout.
+    ClockStretchTimeout,
+    /// The transfer stopped making progress, for instance because the bus is
+    /// held low.
+    Timeout,
+}
+
+/// Configuration for the I2C controller.
+#[derive(Debug, Copy, Clone)]
+pub struct I2cConfig {
+    /// Frequency of the VPU core clock, in Hz. The I2C clock is derived from
+    /// it, so it must match the firmware's `core_freq`.
+    pub core_clock: u32,
+    /// Requested SCL frequency, in Hz.
+    pub frequency: u32,
+    /// How many SCL cycles a slave may stretch the clock for, or `0` to wait
+    /// forever.
+    pub clock_stretch_timeout: u16,
+}
+
+impl Default for I2cConfig {
+    /// 100kHz standard mode with the reset clock-stretch timeout of 64
+    /// cycles, at the default core clock.
+    fn default() -> I2cConfig {
+        I2cConfig {
+            core_clock: DEFAULT_CORE_CLOCK,
+            frequency: 100_000,
+            clock_stretch_timeout: 0x40,
+        }
+    }
+}
+
+impl I2cConfig {
+    /// Returns the value for `DIV` giving the fastest clock no higher than the
+    /// requested frequency, from `frequency = core_clock / divisor` with an
+    /// even divisor from 2 to 65534. Frequencies below `core_clock / 65534`
+    /// get the slowest clock, a divisor of 65534.
+    pub fn divisor(&self) -> u16 {
+        let frequency = self.frequency.max(1) as u64;
+        let divisor = (self.core_clock as u64).div_ceil(frequency);
+        let divisor = (divisor + 1) & !1;
+        match divisor {
+            0..=2 => 2,
+            0xFFFF..=u64::MAX => 0xFFFE,
+            _ => divisor as u16,
+        }
+    }
+
+    /// Returns the SCL frequency actually achieved by `divisor()`.
+    pub fn actual_frequency(&self) -> u32 {
+        self.core_clock / self.divisor() as u32
+    }
+}
+
+/// The Broadcom Serial Controller `BSC1`, an I2C master on GPIO pins 2 and 3.
+///
+/// Slaves are addressed with 7-bit addresses.
+pub struct I2c {
+    registers: &'static mut Registers,
+    /// Returns the current time in microseconds, for transfer timeouts.
+    now: fn() -> u64,
+    /// GPIO pins 2 and 3, returned when the controller is dropped.
+    _pins: Option<[Gpio<Alt>; 2]>,
+}
+
+impl I2c {
+    /// Initializes the controller with the default configuration. See
+    /// `with_config()`.
+    pub fn new() -> I2c {
+        I2c::with_config(I2cConfig::default())
+    }
+
+    /// Initializes the controller by setting GPIO pins 2 and 3 to alternative
+    /// function 0 (`SDA1` and `SCL1`), then programming the clock divisor and
+    /// clock-stretch timeout from `config`. The pins have pull-ups on the
+    /// board.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either GPIO pin is already in use. The pins are returned
+    /// when the `I2c` is dropped.
+    pub fn with_config(config: I2cConfig) -> I2c {
+        let pins = [2, 3].map(|pin| Gpio::new(pin).into_alt(Function::Alt0));
+
+        let mut i2c = unsafe { I2c::at(BSC1_REG_BASE, timer::current_time) };
+        i2c._pins = Some(pins);
+        i2c.configure(&config);
+        i2c
+    }
+
+    /// Returns a handle to BSC registers at `base` without configuring them
+    /// or claiming GPIO pins, timing transfers out with `now`, a microsecond
+    /// clock. Transfers use whatever clock divisor and clock-stretch timeout
+    /// the block already holds.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to a `'static` BSC register block.
+    pub(crate) unsafe fn at(base: usize, now: fn() -> u64) -> I2c {
+        I2c {
+            registers: &mut *(base as *mut Registers),
+            now,
+            _pins: None,
+        }
+    }
+
+    /// Enables the controller, clears the FIFO and status, and programs the
+    /// clock divisor and clock-stretch timeout from `config`.
+    fn configure(&mut self, config: &I2cConfig) {
+        self.registers.C.write(Control::Enable as u32 | Control::Clear as u32);
+        self.registers.S.write(Status::Done as u32 | Status::AckError as u32 | Status::ClockTimeout as u32);
+        self.registers.DIV.write(config.divisor() as u32);
+        self.registers.CLKT.write(config.clock_stretch_timeout as u32);
+    }
+
+    /// Writes `bytes` to the slave at `address`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `address` isn't a 7-bit address or `bytes` is longer than
+    /// 65535 bytes.
+    pub fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.start_write(address, bytes.len());
+        let result = self.send(bytes).and_then(|_| self.finish());
+        self.end(result)
+    }
+
+    /// Reads `buf.len()` bytes from the slave at `address` into `buf`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `address` isn't a 7-bit address or `buf` is longer than
+    /// 65535 bytes.
+    pub fn read(&mut self, address: u8, buf: &mut [u8]) -> Result<(), Error> {
+        self.start_read(address, buf.len());
+        let result = self.receive(buf).and_then(|_| self.finish());
+        self.end(result)
+    }
+
+    /// Writes `bytes` to the slave at `address`, then reads `buf.len()` bytes
+    /// from it into `buf` after a repeated start, without releasing the bus in
+    /// between. Typically used to read registers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `address` isn't a 7-bit address, `bytes` doesn't fit in the
+    /// 16-byte FIFO, or `buf` is longer than 65535 bytes.
+    pub fn write_read(&mut self, address: u8, bytes: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+        assert!(bytes.len() <= FIFO_DEPTH, "I2c::write_read(): write longer than the FIFO");
+        assert!(buf.len() <= 0xFFFF, "I2c::write_read(): read too long");
+
+        self.start_write_read(address, bytes);
+        let result = self.wait_for(Status::TransferActive as u32).and_then(|_| {
+            self.restart_read(buf.len());
+            self.receive(buf)
+        }).and_then(|_| self.finish());
+        self.end(result)
+    }
+
+    /// Sets up and starts a write of `len` bytes to the slave at `address`.
+    fn start_write(&mut self, address: u8, len: usize) {
+        self.begin(address, len);
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32);
+    }
+
+    /// Sets up and starts a read of `len` bytes from the slave at `address`.
+    fn start_read(&mut self, address: u8, len: usize) {
+        self.begin(address, len);
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32 | Control::Read as u32);
+    }
+
+    /// Sets up and starts the write half of a `write_read()`, queueing all of
+    /// `bytes` before starting.
+    fn start_write_read(&mut self, address: u8, bytes: &[u8]) {
+        // The controller issues a repeated start if a read is started while
+        // the write is still active, so the write must be queued up front.
+        self.begin(address, bytes.len());
+        for &byte in bytes {
+            self.registers.FIFO.write(byte as u32);
+        }
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32);
+    }
+
+    /// Starts a read of `len` bytes from the slave addressed by the write in
+    /// progress, which the controller turns into a repeated start.
+    fn restart_read(&mut self, len: usize) {
+        self.registers.DLEN.write(len as u32);
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32 | Control::Read as u32);
+    }
+
+    /// Clears the FIFO and sets up a transfer of `len` bytes with the slave
+    /// at `address`. The status was cleared by `configure()` or by the last
+    /// transfer's `end()`.
+    fn begin(&mut self, address: u8, len: usize) {
+        assert!(address < 0x80, "I2c: {:#x} is not a 7-bit address", address);
+        assert!(len <= 0xFFFF, "I2c: transfer of {} bytes is too long", len);
+
+        self.registers.C.write(Control::Enable as u32 | Control::Clear as u32);
+        self.registers.A.write(address as u32);
+        self.registers.DLEN.write(len as u32);
+    }
+
+    /// Returns the error flagged in `S`, if any.
+    fn error(&self) -> Result<(), Error> {
+        let status = self.registers.S.read();
+        if status & Status::AckError as u32 != 0 {
+            Err(Error::Nack)
+        } else if status & Status::ClockTimeout as u32 != 0 {
+            Err(Error::ClockStretchTimeout)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Waits for any of the `S` bits in `mask` or an error.
+    fn wait_for(&self, mask: u32) -> Result<(), Error> {
+        let deadline = (self.now)() + PROGRESS_TIMEOUT;
+        while self.registers.S.read() & mask == 0 {
+            self.error()?;
+            if (self.now)() > deadline {
+                return Err(Error::Timeout);
+            }
+        }
+
+        self.error()
+    }
+
+    /// Feeds `bytes` to the FIFO as space frees up.
+    fn send(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        for &byte in bytes {
+            self.wait_for(Status::TxSpace as u32)?;
+            self.registers.FIFO.write(byte as u32);
+        }
+
+        Ok(())
+    }
+
+    /// Drains the FIFO into `buf` as bytes arrive.
+    fn receive(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for byte in buf.iter_mut() {
+            self.wait_for(Status::RxData as u32)?;
+            *byte = self.registers.FIFO.read() as u8;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the transfer to complete.
+    fn finish(&mut self) -> Result<(), Error> {
+        self.wait_for(Status::Done as u32)
+    }
+
+    /// Clears the FIFO and status after a transfer ending with `result`.
+    fn end(&mut self, result: Result<(), Error>) -> Result<(), Error> {
+        // On errors the controller sends a stop itself; wait for it so the
+        // next transfer starts on an idle bus.
+        if result.is_err() {
+            let deadline = (self.now)() + PROGRESS_TIMEOUT;
+            while !self.registers.S.has_mask(Status::Done as u32)
+                && (self.now)() <= deadline { }
+        }
+
+        self.registers.C.write(Control::Enable as u32 | Control::Clear as u32);
+        self.registers.S.write(Status::Done as u32 | Status::AckError as u32 | Status::ClockTimeout as u32);
+        result
+    }
+}
@@ -0,0 +1,200 @@
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
+
+use test_util::MockRegs;
+
+use super::{Error, I2c, I2cConfig};
+
+/// Word offsets of the BSC registers used below.
+const C: usize = 0;
+const S: usize = 1;
+const DLEN: usize = 2;
+const A: usize = 3;
+const FIFO: usize = 4;
+const DIV: usize = 5;
+const CLKT: usize = 7;
+
+/// `C` bits checked below.
+const READ: u32 = 1 << 0;
+const CLEAR: u32 = 0b11 << 4;
+const START: u32 = 1 << 7;
+const ENABLE: u32 = 1 << 15;
+
+/// `S` bits set by the mock controller below.
+const TA: u32 = 1 << 0;
+const DONE: u32 = 1 << 1;
+const TXD: u32 = 1 << 4;
+const RXD: u32 = 1 << 5;
+const ERR: u32 = 1 << 8;
+const CLKT_ERR: u32 = 1 << 9;
+
+/// A fake microsecond clock that jumps a millisecond every time it's read, so
+/// a transfer that makes no progress times out after a few dozen polls.
+fn clock() -> u64 {
+    static NOW: AtomicU64 = AtomicU64::new(0);
+    NOW.fetch_add(1_000, Relaxed)
+}
+
+fn config(frequency: u32) -> I2cConfig {
+    I2cConfig { core_clock: 250_000_000, frequency: frequency, ..I2cConfig::default() }
+}
+
+#[test]
+fn divisor() {
+    assert_eq!(config(100_000).divisor(), 2500);
+    assert_eq!(config(100_000).actual_frequency(), 100_000);
+
+    // Rounded up to the next even divisor, so never faster than requested.
+    assert_eq!(config(400_000).divisor(), 626);
+    assert!(config(400_000).actual_frequency() <= 400_000);
+
+    assert_eq!(config(250_000_000).divisor(), 2);
+    assert_eq!(config(500_000_000).divisor(), 2);
+    assert_eq!(config(500_000_000).actual_frequency(), 125_000_000);
+}
+
+#[test]
+fn slow_divisor() {
+    // Divisors above 32768 are written as-is.
+    assert_eq!(config(7_000).divisor(), 35716);
+    assert!(config(7_000).actual_frequency() <= 7_000);
+
+    let exact = I2cConfig { core_clock: 65_534_000, frequency: 1_000, ..I2cConfig::default() };
+    assert_eq!(exact.divisor(), 0xFFFE);
+    assert_eq!(exact.actual_frequency(), 1_000);
+
+    // Anything slower gets the slowest clock.
+    let slower = I2cConfig { core_clock: 65_535_000, ..exact };
+    assert_eq!(slower.divisor(), 0xFFFE);
+    assert_eq!(config(0).divisor(), 0xFFFE);
+    assert_eq!(config(0).actual_frequency(), 250_000_000 / 0xFFFE);
+}
+
+#[test]
+fn configure() {
+    let regs = MockRegs::<8>::new();
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    i2c.configure(&I2cConfig { clock_stretch_timeout: 0x100, ..config(100_000) });
+    assert_eq!(regs[C], ENABLE | CLEAR);
+    assert_eq!(regs[DIV], 2500);
+    assert_eq!(regs[CLKT], 0x100);
+}
+
+#[test]
+fn write() {
+    let regs = MockRegs::<8>::new();
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    i2c.start_write(0x48, 3);
+    assert_eq!(regs[A], 0x48);
+    assert_eq!(regs[DLEN], 3);
+    assert_eq!(regs[C], ENABLE | START);
+}
+
+#[test]
+fn read() {
+    let regs = MockRegs::<8>::new();
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    i2c.start_read(0x7F, 0xFFFF);
+    assert_eq!(regs[A], 0x7F);
+    assert_eq!(regs[DLEN], 0xFFFF);
+    assert_eq!(regs[C], ENABLE | START | READ);
+}
+
+#[test]
+fn write_read() {
+    let regs = MockRegs::<8>::new();
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    i2c.start_write_read(0x68, &[0x3B, 0x75]);
+    assert_eq!(regs[A], 0x68);
+    assert_eq!(regs[DLEN], 2);
+    assert_eq!(regs[FIFO], 0x75);
+    assert_eq!(regs[C], ENABLE | START);
+
+    i2c.restart_read(6);
+    assert_eq!(regs[A], 0x68);
+    assert_eq!(regs[DLEN], 6);
+    assert_eq!(regs[C], ENABLE | START | READ);
+}
+
+
+#[test]
+fn write_transfer() {
+    let mut regs = MockRegs::<8>::new();
+    regs[S] = TXD | DONE;
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    assert_eq!(i2c.write(0x48, &[0x01, 0x60]), Ok(()));
+    assert_eq!(regs[A], 0x48);
+    assert_eq!(regs[DLEN], 2);
+    assert_eq!(regs[FIFO], 0x60);
+
+    // The FIFO and status are cleared afterwards.
+    assert_eq!(regs[C], ENABLE | CLEAR);
+    assert_eq!(regs[S], DONE | ERR | CLKT_ERR);
+}
+
+#[test]
+fn read_transfer() {
+    let mut regs = MockRegs::<8>::new();
+    regs[S] = RXD | DONE;
+    regs[FIFO] = 0x5A;
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    let mut buf = [0; 3];
+    assert_eq!(i2c.read(0x50, &mut buf), Ok(()));
+    assert_eq!(buf, [0x5A; 3]);
+    assert_eq!(regs[A], 0x50);
+    assert_eq!(regs[DLEN], 3);
+}
+
+#[test]
+fn write_read_transfer() {
+    let mut regs = MockRegs::<8>::new();
+    regs[S] = TA | RXD | DONE;
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    // The mock FIFO reads back the last byte written to it.
+    let mut buf = [0; 2];
+    assert_eq!(i2c.write_read(0x68, &[0x3B], &mut buf), Ok(()));
+    assert_eq!(buf, [0x3B; 2]);
+    assert_eq!(regs[A], 0x68);
+    assert_eq!(regs[DLEN], 2);
+}
+
+#[test]
+fn nack() {
+    let mut regs = MockRegs::<8>::new();
+    regs[S] = ERR | DONE;
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    assert_eq!(i2c.write(0x48, &[0x01]), Err(Error::Nack));
+
+    regs[S] = ERR | DONE;
+    assert_eq!(i2c.read(0x48, &mut [0; 1]), Err(Error::Nack));
+}
+
+#[test]
+fn clock_stretch_timeout() {
+    let mut regs = MockRegs::<8>::new();
+    regs[S] = CLKT_ERR | DONE;
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+
+    assert_eq!(i2c.write_read(0x48, &[0x01], &mut [0; 1]), Err(Error::ClockStretchTimeout));
+    assert_eq!(regs[S], DONE | ERR | CLKT_ERR);
+}
+
+#[test]
+fn no_progress() {
+    // Neither FIFO space nor completion ever shows up.
+    let mut regs = MockRegs::<8>::new();
+    let mut i2c = unsafe { I2c::at(regs.base(), clock) };
+    assert_eq!(i2c.write(0x48, &[0x01]), Err(Error::Timeout));
+
+    // Undo the mock's copy of the status clear.
+    regs[S] = 0;
+    assert_eq!(i2c.read(0x48, &mut [0; 1]), Err(Error::Timeout));
+}
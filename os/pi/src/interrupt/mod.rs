@@ -0,0 +1,211 @@
+use common::IO_BASE;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile};
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the ARM interrupt controller registers.
+const INT_BASE: usize = IO_BASE + 0xB200;
+
+/// An interrupt source routed through the ARM interrupt controller.
+///
+/// Variants numbered `0` to `63` are the GPU peripheral IRQs from page 113 of
+/// the BCM2837 documentation and share their number with the bit in the
+/// `IRQ pending 1/2` registers. The remaining variants are the ARM-specific
+/// "basic" sources, numbered `64` plus their bit in `IRQ basic pending`.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interrupt {
+    Timer0 = 0,
+    Timer1 = 1,
+    Timer2 = 2,
+    Timer3 = 3,
+    Usb = 9,
+    Dma0 = 16,
+    Dma1 = 17,
+    Dma2 = 18,
+    Dma3 = 19,
+    Dma4 = 20,
+    Dma5 = 21,
+    Dma6 = 22,
+    Dma7 = 23,
+    Dma8 = 24,
+    Dma9 = 25,
+    Dma10 = 26,
+    /// Shared by DMA channels 11 to 14.
+    DmaShared = 27,
+    Aux = 29,
+    I2cSpiSlave = 43,
+    Pwa0 = 45,
+    Pwa1 = 46,
+    Smi = 48,
+    Gpio0 = 49,
+    Gpio1 = 50,
+    Gpio2 = 51,
+    Gpio3 = 52,
+    I2c = 53,
+    Spi = 54,
+    Pcm = 55,
+    Uart = 57,
+    Emmc = 62,
+
+    ArmTimer = 64,
+    ArmMailbox = 65,
+    ArmDoorbell0 = 66,
+    ArmDoorbell1 = 67,
+    Gpu0Halted = 68,
+    Gpu1Halted = 69,
+    IllegalAccess1 = 70,
+    IllegalAccess0 = 71,
+}
+
+/// Number of the first basic (ARM-specific) interrupt source.
+const BASIC_START: u8 = 64;
+
+impl Interrupt {
+    /// Every interrupt source known to this module.
+    pub const ALL: [Interrupt; 39] = [
+        Interrupt::Timer0, Interrupt::Timer1, Interrupt::Timer2, Interrupt::Timer3,
+        Interrupt::Usb, Interrupt::Dma0, Interrupt::Dma1, Interrupt::Dma2,
+        Interrupt::Dma3, Interrupt::Dma4, Interrupt::Dma5, Interrupt::Dma6,
+        Interrupt::Dma7, Interrupt::Dma8, Interrupt::Dma9, Interrupt::Dma10,
+        Interrupt::DmaShared, Interrupt::Aux, Interrupt::I2cSpiSlave, Interrupt::Pwa0,
+        Interrupt::Pwa1, Interrupt::Smi, Interrupt::Gpio0, Interrupt::Gpio1,
+        Interrupt::Gpio2, Interrupt::Gpio3, Interrupt::I2c, Interrupt::Spi,
+        Interrupt::Pcm, Interrupt::Uart, Interrupt::Emmc, Interrupt::ArmTimer,
+        Interrupt::ArmMailbox, Interrupt::ArmDoorbell0, Interrupt::ArmDoorbell1,
+        Interrupt::Gpu0Halted, Interrupt::Gpu1Halted, Interrupt::IllegalAccess1,
+        Interrupt::IllegalAccess0,
+    ];
+
+    /// Returns the interrupt numbered `number`, if it is a known source.
+    pub fn from_number(number: u8) -> Option<Interrupt> {
+        Interrupt::ALL.iter().cloned().find(|int| *int as u8 == number)
+    }
+
+    /// Returns `true` if this is one of the ARM-specific basic sources.
+    pub fn is_basic(self) -> bool {
+        self as u8 >= BASIC_START
+    }
+
+    /// Returns the index of the register in a bank (`0` or `1` for GPU
+    /// sources, `2` for basic ones) and the mask of this interrupt's bit.
+    fn location(self) -> (usize, u32) {
+        let number = self as u8;
+        if self.is_basic() {
+            (2, 1 << (number - BASIC_START))
+        } else {
+            ((number / 32) as usize, 1 << (number % 32))
+        }
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    IRQ_BASIC_PENDING: ReadVolatile<u32>,
+    IRQ_PENDING: [ReadVolatile<u32>; 2],
+    FIQ_CONTROL: Volatile<u32>,
+    ENABLE_IRQS: [Volatile<u32>; 2],
+    ENABLE_BASIC_IRQS: Volatile<u32>,
+    DISABLE_IRQS: [Volatile<u32>; 2],
+    DISABLE_BASIC_IRQS: Volatile<u32>,
+}
+
+/// Mask of the basic pending bits that correspond to ARM-specific sources;
+/// the upper bits summarize or duplicate GPU sources.
+const BASIC_SOURCES_MASK: u32 = 0xFF;
+
+/// An interrupt controller. Used to enable and disable interrupts as well as
+/// to check if an interrupt is pending.
+pub struct Controller {
+    registers: &'static mut Registers
+}
+
+impl Controller {
+    /// Returns a new handle to the interrupt controller.
+    pub fn new() -> Controller {
+        Controller {
+            registers: unsafe { &mut *(INT_BASE as *mut Registers) },
+        }
+    }
+
+    /// Enables the interrupt `int`.
+    pub fn enable(&mut self, int: Interrupt) {
+        // Writing 0 bits has no effect, so there's no read-modify-write here.
+        match int.location() {
+            (2, mask) => self.registers.ENABLE_BASIC_IRQS.write(mask),
+            (i, mask) => self.registers.ENABLE_IRQS[i].write(mask),
+        }
+    }
+
+    /// Disables the interrupt `int`.
+    pub fn disable(&mut self, int: Interrupt) {
+        match int.location() {
+            (2, mask) => self.registers.DISABLE_BASIC_IRQS.write(mask),
+            (i, mask) => self.registers.DISABLE_IRQS[i].write(mask),
+        }
+    }
+
+    /// Disables every interrupt source.
+    pub fn disable_all(&mut self) {
+        self.registers.DISABLE_BASIC_IRQS.write(!0);
+        self.registers.DISABLE_IRQS[0].write(!0);
+        self.registers.DISABLE_IRQS[1].write(!0);
+    }
+
+    /// Returns `true` if `int` is pending. Returns `false` otherwise.
+    pub fn is_pending(&self, int: Interrupt) -> bool {
+        match int.location() {
+            (2, mask) => self.registers.IRQ_BASIC_PENDING.has_mask(mask),
+            (i, mask) => self.registers.IRQ_PENDING[i].has_mask(mask),
+        }
+    }
+
+    /// Returns an iterator over the known interrupt sources that are pending
+    /// right now, in ascending order of their number.
+    ///
+    /// The pending registers are read once, when this method is called.
+    pub fn pending(&self) -> Pending {
+        Pending {
+            bits: [
+                self.registers.IRQ_PENDING[0].read(),
+                self.registers.IRQ_PENDING[1].read(),
+                self.registers.IRQ_BASIC_PENDING.read() & BASIC_SOURCES_MASK,
+            ],
+        }
+    }
+}
+
+/// An iterator over a snapshot of pending interrupts. See
+/// `Controller::pending()`.
+#[derive(Debug, Clone)]
+pub struct Pending {
+    bits: [u32; 3],
+}
+
+impl Iterator for Pending {
+    type Item = Interrupt;
+
+    fn next(&mut self) -> Option<Interrupt> {
+        for i in 0..self.bits.len() {
+            while self.bits[i] != 0 {
+                let bit = self.bits[i].trailing_zeros();
+                self.bits[i] &= !(1 << bit);
+
+                // Sources without a variant are skipped.
+                let number = match i {
+                    2 => BASIC_START + bit as u8,
+                    _ => (i * 32) as u8 + bit as u8,
+                };
+                if let Some(int) = Interrupt::from_number(number) {
+                    return Some(int);
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,69 @@
+use std::vec::Vec;
+
+use super::{Interrupt, Pending, BASIC_START};
+
+#[test]
+fn from_number() {
+    for &int in Interrupt::ALL.iter() {
+        assert_eq!(Interrupt::from_number(int as u8), Some(int));
+    }
+
+    assert_eq!(Interrupt::from_number(4), None);
+    assert_eq!(Interrupt::from_number(63), None);
+    assert_eq!(Interrupt::from_number(72), None);
+    assert_eq!(Interrupt::from_number(255), None);
+}
+
+#[test]
+fn location() {
+    assert_eq!(Interrupt::Timer1.location(), (0, 1 << 1));
+    assert_eq!(Interrupt::Aux.location(), (0, 1 << 29));
+    assert_eq!(Interrupt::Uart.location(), (1, 1 << 25));
+    assert_eq!(Interrupt::Emmc.location(), (1, 1 << 30));
+    assert_eq!(Interrupt::ArmTimer.location(), (2, 1 << 0));
+    assert_eq!(Interrupt::IllegalAccess0.location(), (2, 1 << 7));
+
+    // Every source maps back to its own number.
+    for &int in Interrupt::ALL.iter() {
+        let (i, mask) = int.location();
+        assert_eq!(mask.count_ones(), 1);
+        let number = match i {
+            2 => BASIC_START + mask.trailing_zeros() as u8,
+            _ => (i * 32) as u8 + mask.trailing_zeros() as u8,
+        };
+        assert_eq!(number, int as u8);
+        assert_eq!(int.is_basic(), i == 2);
+    }
+}
+
+#[test]
+fn pending() {
+    let snapshot = Pending {
+        bits: [
+            1 << 1 | 1 << 4 | 1 << 29,
+            1 << 25 | 1 << 31,
+            1 << 0 | 1 << 7 | 1 << 8,
+        ],
+    };
+
+    // Bits 4, 63 and basic bit 8 have no variant and are skipped.
+    let pending: Vec<Interrupt> = snapshot.collect();
+    assert_eq!(pending, [
+        Interrupt::Timer1, Interrupt::Aux, Interrupt::Uart,
+        Interrupt::ArmTimer, Interrupt::IllegalAccess0,
+    ]);
+}
+
+#[test]
+fn pending_every_source() {
+    let snapshot = Pending { bits: [!0, !0, 0xFF] };
+    let pending: Vec<Interrupt> = snapshot.collect();
+    assert_eq!(pending, Interrupt::ALL);
+}
+
+#[test]
+fn nothing_pending() {
+    let mut snapshot = Pending { bits: [0; 3] };
+    assert_eq!(snapshot.next(), None);
+    assert_eq!(snapshot.next(), None);
+}
@@ -11,10 +11,36 @@
 // #[cfg(feature = "std")]
 // extern crate core;
 extern crate volatile;
+#[cfg(feature = "hal")]
+extern crate embedded_hal;
+#[cfg(feature = "hal")]
+extern crate nb;
+#[cfg(feature = "rand_core")]
+extern crate rand_core;
 
 extern crate std;
 pub mod timer;
 pub mod uart;
+pub mod pl011;
 pub mod gpio;
 pub mod common;
-pub mod atags;
\ No newline at end of file
+pub mod atags;
+pub mod fdt;
+pub mod boot;
+pub mod interrupt;
+pub mod generic_timer;
+pub mod mailbox;
+pub mod framebuffer;
+pub mod block;
+pub mod emmc;
+pub mod dma;
+pub mod spi;
+pub mod i2c;
+pub mod clock;
+pub mod pwm;
+pub mod power;
+pub mod rng;
+#[cfg(feature = "hal")]
+pub mod hal;
+#[cfg(test)]
+mod test_util;
\ No newline at end of file
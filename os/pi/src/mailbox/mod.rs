@@ -0,0 +1,576 @@
+use core::marker::PhantomData;
+use core::sync::atomic::{fence, Ordering};
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+use common::{IO_BASE, bus_address};
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the VideoCore mailbox registers.
+const MAILBOX_BASE: usize = IO_BASE + 0xB880;
+
+/// `STATUS` bit set while a mailbox can't accept another message.
+const STATUS_FULL: u32 = 1 << 31;
+
+/// `STATUS` bit set while a mailbox holds no message.
+const STATUS_EMPTY: u32 = 1 << 30;
+
+/// Number of 32-bit words in a `PropertyBuffer`.
+const BUFFER_WORDS: usize = 256;
+
+/// Buffer code of a request.
+const CODE_REQUEST: u32 = 0;
+
+/// Buffer code of a response the firmware processed successfully.
+const CODE_SUCCESS: u32 = 0x80000000;
+
+/// Tag request/response code bit set by the firmware on tags it handled.
+const TAG_RESPONSE: u32 = 1 << 31;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // Mailbox 0, VideoCore to ARM.
+    READ: ReadVolatile<u32>,
+    __r0: [Reserved<u32>; 3],
+    PEEK: ReadVolatile<u32>,
+    SENDER: ReadVolatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONFIG: Volatile<u32>,
+    // Mailbox 1, ARM to VideoCore.
+    WRITE: Volatile<u32>,
+    __r1: [Reserved<u32>; 5],
+    WRITE_STATUS: ReadVolatile<u32>,
+}
+
+/// A mailbox channel.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    Power = 0,
+    Framebuffer = 1,
+    VirtualUart = 2,
+    Vchiq = 3,
+    Leds = 4,
+    Buttons = 5,
+    TouchScreen = 6,
+    /// Property tags, ARM to VideoCore. See `PropertyBuffer`.
+    Property = 8,
+}
+
+/// Errors from a property tag request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The tags don't fit in a `PropertyBuffer`.
+    BufferFull,
+    /// The firmware couldn't parse the request buffer.
+    Failed,
+    /// The firmware didn't handle the tag, typically because it doesn't know it.
+    Unhandled,
+}
+
+/// The VideoCore mailbox, through which the ARM core talks to the firmware.
+pub struct Mailbox {
+    registers: &'static mut Registers,
+}
+
+impl Mailbox {
+    /// Returns a new handle to the mailbox.
+    pub fn new() -> Mailbox {
+        Mailbox {
+            registers: unsafe { &mut *(MAILBOX_BASE as *mut Registers) },
+        }
+    }
+
+    /// Sends `data` on `channel`, blocking while the outgoing mailbox is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lowest 4 bits of `data` aren't zero; they hold the
+    /// channel number.
+    pub fn write(&mut self, channel: Channel, data: u32) {
+        if data & 0xF != 0 {
+            panic!("Mailbox::write(): data {:#x} is not 16-byte aligned", data);
+        }
+
+        while self.registers.WRITE_STATUS.has_mask(STATUS_FULL) { }
+        self.registers.WRITE.write(data | channel as u32);
+    }
+
+    /// Blocks until a message arrives on `channel` and returns its data, with
+    /// the channel bits cleared. Messages for other channels are discarded.
+    pub fn read(&mut self, channel: Channel) -> u32 {
+        loop {
+            while self.registers.STATUS.has_mask(STATUS_EMPTY) { }
+            let message = self.registers.READ.read();
+            if message & 0xF == channel as u32 {
+                return message & !0xF;
+            }
+        }
+    }
+
+    /// Sends `data` on `channel` and waits for the reply.
+    pub fn call(&mut self, channel: Channel, data: u32) -> u32 {
+        self.write(channel, data);
+        self.read(channel)
+    }
+}
+
+/// A property tag: a request to the firmware and the response it fills in.
+pub trait Tag {
+    /// The tag identifier.
+    const ID: u32;
+    /// Size of the tag's value buffer in words: the larger of the request and
+    /// the response.
+    const LEN: usize;
+    /// The decoded response.
+    type Response;
+
+    /// Writes the request into `value`, which is `LEN` zeroed words.
+    fn encode(&self, _value: &mut [u32]) { }
+
+    /// Decodes the response from `value`, which is `LEN` words.
+    fn decode(value: &[u32]) -> Self::Response;
+}
+
+/// A handle to a tag added to a `PropertyBuffer`, used to get its response.
+#[derive(Debug)]
+pub struct Slot<T> {
+    offset: usize,
+    _tag: PhantomData<T>,
+}
+
+#[repr(C, align(16))]
+struct Words([u32; BUFFER_WORDS]);
+
+/// A property tag buffer for the `Property` channel.
+///
+/// Add tags with `push()`, send them to the firmware in one go with `send()`
+/// and then read each tag's response with `get()`.
+pub struct PropertyBuffer {
+    words: Words,
+    len: usize,
+}
+
+impl PropertyBuffer {
+    /// Returns an empty property buffer.
+    pub fn new() -> PropertyBuffer {
+        PropertyBuffer {
+            words: Words([0; BUFFER_WORDS]),
+            // The total size and the request code come first.
+            len: 2,
+        }
+    }
+
+    /// Appends the request for `tag`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BufferFull` if the tag and the end tag don't fit.
+    pub fn push<T: Tag>(&mut self, tag: &T) -> Result<Slot<T>, Error> {
+        let offset = self.len;
+        let end = offset + 3 + T::LEN;
+        if end + 1 > BUFFER_WORDS {
+            return Err(Error::BufferFull);
+        }
+
+        let words = &mut self.words.0;
+        words[offset] = T::ID;
+        words[offset + 1] = (T::LEN * 4) as u32;
+        words[offset + 2] = 0;
+        for word in &mut words[offset + 3..end] {
+            *word = 0;
+        }
+        tag.encode(&mut words[offset + 3..end]);
+
+        self.len = end;
+        Ok(Slot { offset, _tag: PhantomData })
+    }
+
+    /// Sends the buffer to the firmware through `mailbox` and waits until it
+    /// has been processed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Failed` if the firmware reports an error for the buffer.
+    pub fn send(&mut self, mailbox: &mut Mailbox) -> Result<(), Error> {
+        self.finish();
+
+        // Make the request visible to the GPU before handing it over and the
+        // response visible to us afterwards.
+        let address = bus_address(self.words.0.as_ptr() as usize);
+        fence(Ordering::SeqCst);
+        mailbox.call(Channel::Property, address);
+        fence(Ordering::SeqCst);
+
+        self.status()
+    }
+
+    /// Writes the end tag, the total size and the request code.
+    fn finish(&mut self) {
+        let words = &mut self.words.0;
+        words[self.len] = 0;
+        words[0] = ((self.len + 1) * 4) as u32;
+        words[1] = CODE_REQUEST;
+    }
+
+    /// Returns whether the firmware processed the buffer successfully.
+    fn status(&self) -> Result<(), Error> {
+        let code = unsafe { ::core::ptr::read_volatile(&self.words.0[1]) };
+        match code {
+            CODE_SUCCESS => Ok(()),
+            _ => Err(Error::Failed),
+        }
+    }
+
+    /// Returns the response to the tag in `slot`, after `send()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Unhandled` if the firmware didn't respond to the tag.
+    pub fn get<T: Tag>(&self, slot: &Slot<T>) -> Result<T::Response, Error> {
+        let words = &self.words.0;
+        let code = unsafe { ::core::ptr::read_volatile(&words[slot.offset + 2]) };
+        if code & TAG_RESPONSE == 0 {
+            return Err(Error::Unhandled);
+        }
+
+        let start = slot.offset + 3;
+        Ok(T::decode(&words[start..start + T::LEN]))
+    }
+}
+
+/// Sends the single property tag `tag` and returns its response.
+pub fn property<T: Tag>(tag: &T) -> Result<T::Response, Error> {
+    let mut buffer = PropertyBuffer::new();
+    let slot = buffer.push(tag)?;
+    buffer.send(&mut Mailbox::new())?;
+    buffer.get(&slot)
+}
+
+/// A clock managed by the firmware.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Clock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+/// A device whose power the firmware controls.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Device {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+/// A range of memory, as reported for the ARM and VideoCore splits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryRange {
+    pub base: u32,
+    pub size: u32,
+}
+
+/// The power state of a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PowerState {
+    /// The device is powered on.
+    pub on: bool,
+    /// The device exists.
+    pub exists: bool,
+}
+
+impl PowerState {
+    fn decode(state: u32) -> PowerState {
+        PowerState { on: state & 1 != 0, exists: state & 2 == 0 }
+    }
+}
+
+/// Returns a tag's response as a `u64` from two little-endian words.
+fn u64_from(words: &[u32]) -> u64 {
+    words[0] as u64 | (words[1] as u64) << 32
+}
+
+/// Gets the firmware revision.
+#[derive(Debug, Copy, Clone)]
+pub struct GetFirmwareRevision;
+
+impl Tag for GetFirmwareRevision {
+    const ID: u32 = 0x00000001;
+    const LEN: usize = 1;
+    type Response = u32;
+    fn decode(value: &[u32]) -> u32 { value[0] }
+}
+
+/// Gets the board model.
+#[derive(Debug, Copy, Clone)]
+pub struct GetBoardModel;
+
+impl Tag for GetBoardModel {
+    const ID: u32 = 0x00010001;
+    const LEN: usize = 1;
+    type Response = u32;
+    fn decode(value: &[u32]) -> u32 { value[0] }
+}
+
+/// Gets the board revision code.
+#[derive(Debug, Copy, Clone)]
+pub struct GetBoardRevision;
+
+impl Tag for GetBoardRevision {
+    const ID: u32 = 0x00010002;
+    const LEN: usize = 1;
+    type Response = u32;
+    fn decode(value: &[u32]) -> u32 { value[0] }
+}
+
+/// Gets the MAC address of the on-board Ethernet.
+#[derive(Debug, Copy, Clone)]
+pub struct GetMacAddress;
+
+impl Tag for GetMacAddress {
+    const ID: u32 = 0x00010003;
+    const LEN: usize = 2;
+    type Response = [u8; 6];
+    fn decode(value: &[u32]) -> [u8; 6] {
+        let (a, b) = (value[0], value[1]);
+        [a as u8, (a >> 8) as u8, (a >> 16) as u8, (a >> 24) as u8, b as u8, (b >> 8) as u8]
+    }
+}
+
+/// Gets the board serial number.
+#[derive(Debug, Copy, Clone)]
+pub struct GetBoardSerial;
+
+impl Tag for GetBoardSerial {
+    const ID: u32 = 0x00010004;
+    const LEN: usize = 2;
+    type Response = u64;
+    fn decode(value: &[u32]) -> u64 { u64_from(value) }
+}
+
+/// Gets the range of memory assigned to the ARM cores.
+#[derive(Debug, Copy, Clone)]
+pub struct GetArmMemory;
+
+impl Tag for GetArmMemory {
+    const ID: u32 = 0x00010005;
+    const LEN: usize = 2;
+    type Response = MemoryRange;
+    fn decode(value: &[u32]) -> MemoryRange { MemoryRange { base: value[0], size: value[1] } }
+}
+
+/// Gets the range of memory assigned to the VideoCore.
+#[derive(Debug, Copy, Clone)]
+pub struct GetVcMemory;
+
+impl Tag for GetVcMemory {
+    const ID: u32 = 0x00010006;
+    const LEN: usize = 2;
+    type Response = MemoryRange;
+    fn decode(value: &[u32]) -> MemoryRange { MemoryRange { base: value[0], size: value[1] } }
+}
+
+/// Gets the power state of a device.
+#[derive(Debug, Copy, Clone)]
+pub struct GetPowerState(pub Device);
+
+impl Tag for GetPowerState {
+    const ID: u32 = 0x00020001;
+    const LEN: usize = 2;
+    type Response = PowerState;
+    fn encode(&self, value: &mut [u32]) { value[0] = self.0 as u32; }
+    fn decode(value: &[u32]) -> PowerState { PowerState::decode(value[1]) }
+}
+
+/// Powers a device on or off, optionally waiting until it is stable.
+#[derive(Debug, Copy, Clone)]
+pub struct SetPowerState {
+    pub device: Device,
+    pub on: bool,
+    pub wait: bool,
+}
+
+impl Tag for SetPowerState {
+    const ID: u32 = 0x00028001;
+    const LEN: usize = 2;
+    type Response = PowerState;
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.device as u32;
+        value[1] = self.on as u32 | (self.wait as u32) << 1;
+    }
+    fn decode(value: &[u32]) -> PowerState { PowerState::decode(value[1]) }
+}
+
+/// Gets the current rate of a clock in Hz.
+#[derive(Debug, Copy, Clone)]
+pub struct GetClockRate(pub Clock);
+
+impl Tag for GetClockRate {
+    const ID: u32 = 0x00030002;
+    const LEN: usize = 2;
+    type Response = u32;
+    fn encode(&self, value: &mut [u32]) { value[0] = self.0 as u32; }
+    fn decode(value: &[u32]) -> u32 { value[1] }
+}
+
+/// Gets the maximum rate of a clock in Hz.
+#[derive(Debug, Copy, Clone)]
+pub struct GetMaxClockRate(pub Clock);
+
+impl Tag for GetMaxClockRate {
+    const ID: u32 = 0x00030004;
+    const LEN: usize = 2;
+    type Response = u32;
+    fn encode(&self, value: &mut [u32]) { value[0] = self.0 as u32; }
+    fn decode(value: &[u32]) -> u32 { value[1] }
+}
+
+/// Sets the rate of a clock in Hz. Responds with the rate actually set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetClockRate {
+    pub clock: Clock,
+    pub rate: u32,
+    /// Don't raise the other turbo clocks along with the ARM clock.
+    pub skip_turbo: bool,
+}
+
+impl Tag for SetClockRate {
+    const ID: u32 = 0x00038002;
+    const LEN: usize = 3;
+    type Response = u32;
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.clock as u32;
+        value[1] = self.rate;
+        value[2] = self.skip_turbo as u32;
+    }
+    fn decode(value: &[u32]) -> u32 { value[1] }
+}
+
+/// Gets the SoC temperature in thousandths of a degree Celsius.
+#[derive(Debug, Copy, Clone)]
+pub struct GetTemperature;
+
+impl Tag for GetTemperature {
+    const ID: u32 = 0x00030006;
+    const LEN: usize = 2;
+    type Response = u32;
+    fn decode(value: &[u32]) -> u32 { value[1] }
+}
+
+/// Gets the temperature at which the firmware throttles the SoC, in
+/// thousandths of a degree Celsius.
+#[derive(Debug, Copy, Clone)]
+pub struct GetMaxTemperature;
+
+impl Tag for GetMaxTemperature {
+    const ID: u32 = 0x0003000A;
+    const LEN: usize = 2;
+    type Response = u32;
+    fn decode(value: &[u32]) -> u32 { value[1] }
+}
+
+/// A width and height in pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Sets the size of the display in pixels. Responds with the size set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetPhysicalSize(pub Size);
+
+impl Tag for SetPhysicalSize {
+    const ID: u32 = 0x00048003;
+    const LEN: usize = 2;
+    type Response = Size;
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.0.width;
+        value[1] = self.0.height;
+    }
+    fn decode(value: &[u32]) -> Size { Size { width: value[0], height: value[1] } }
+}
+
+/// Sets the size of the framebuffer in pixels, which may be larger than the
+/// display. Responds with the size set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetVirtualSize(pub Size);
+
+impl Tag for SetVirtualSize {
+    const ID: u32 = 0x00048004;
+    const LEN: usize = 2;
+    type Response = Size;
+    fn encode(&self, value: &mut [u32]) {
+        value[0] = self.0.width;
+        value[1] = self.0.height;
+    }
+    fn decode(value: &[u32]) -> Size { Size { width: value[0], height: value[1] } }
+}
+
+/// Sets the framebuffer depth in bits per pixel. Responds with the depth set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetDepth(pub u32);
+
+impl Tag for SetDepth {
+    const ID: u32 = 0x00048005;
+    const LEN: usize = 1;
+    type Response = u32;
+    fn encode(&self, value: &mut [u32]) { value[0] = self.0; }
+    fn decode(value: &[u32]) -> u32 { value[0] }
+}
+
+/// Sets the pixel order: `0` for BGR, `1` for RGB. Responds with the order
+/// set.
+#[derive(Debug, Copy, Clone)]
+pub struct SetPixelOrder(pub u32);
+
+impl Tag for SetPixelOrder {
+    const ID: u32 = 0x00048006;
+    const LEN: usize = 1;
+    type Response = u32;
+    fn encode(&self, value: &mut [u32]) { value[0] = self.0; }
+    fn decode(value: &[u32]) -> u32 { value[0] }
+}
+
+/// Allocates the framebuffer with the given alignment in bytes. Responds with
+/// its bus address and size in bytes.
+#[derive(Debug, Copy, Clone)]
+pub struct AllocateBuffer(pub u32);
+
+impl Tag for AllocateBuffer {
+    const ID: u32 = 0x00040001;
+    const LEN: usize = 2;
+    type Response = MemoryRange;
+    fn encode(&self, value: &mut [u32]) { value[0] = self.0; }
+    fn decode(value: &[u32]) -> MemoryRange { MemoryRange { base: value[0], size: value[1] } }
+}
+
+/// Gets the number of bytes per framebuffer row.
+#[derive(Debug, Copy, Clone)]
+pub struct GetPitch;
+
+impl Tag for GetPitch {
+    const ID: u32 = 0x00040008;
+    const LEN: usize = 1;
+    type Response = u32;
+    fn decode(value: &[u32]) -> u32 { value[0] }
+}
@@ -0,0 +1,69 @@
+use super::{Clock, Error, GetClockRate, GetMacAddress, GetPitch, PropertyBuffer, SetClockRate,
+            BUFFER_WORDS, CODE_SUCCESS, TAG_RESPONSE};
+
+/// Fills in a response to the tag at `offset` the way the firmware does:
+/// `TAG_RESPONSE` and the response length in the request/response code, and
+/// the response itself in the value buffer.
+fn respond(buffer: &mut PropertyBuffer, offset: usize, value: &[u32]) {
+    let words = &mut buffer.words.0;
+    words[offset + 2] = TAG_RESPONSE | (value.len() * 4) as u32;
+    words[offset + 3..offset + 3 + value.len()].copy_from_slice(value);
+}
+
+#[test]
+fn request() {
+    let mut buffer = PropertyBuffer::new();
+    buffer.push(&GetClockRate(Clock::Emmc)).unwrap();
+    buffer.push(&SetClockRate { clock: Clock::Arm, rate: 1_200_000_000, skip_turbo: true }).unwrap();
+    buffer.finish();
+
+    // Sizes are in bytes and cover whole words, up to and including the end
+    // tag.
+    assert_eq!(buffer.words.0[..14], [
+        14 * 4, 0,
+        0x00030002, 2 * 4, 0, 1, 0,
+        0x00038002, 3 * 4, 0, 3, 1_200_000_000, 1,
+        0,
+    ]);
+}
+
+#[test]
+fn value_buffers_are_zeroed() {
+    let mut buffer = PropertyBuffer::new();
+    buffer.words.0[..8].copy_from_slice(&[!0; 8]);
+    buffer.push(&GetMacAddress).unwrap();
+
+    // A tag without a request still gets `LEN` words for its response.
+    assert_eq!(buffer.words.0[2..7], [0x00010003, 2 * 4, 0, 0, 0]);
+}
+
+#[test]
+fn response() {
+    let mut buffer = PropertyBuffer::new();
+    let clock = buffer.push(&GetClockRate(Clock::Emmc)).unwrap();
+    let pitch = buffer.push(&GetPitch).unwrap();
+    let mac = buffer.push(&GetMacAddress).unwrap();
+    buffer.finish();
+    assert_eq!(buffer.status(), Err(Error::Failed));
+
+    buffer.words.0[1] = CODE_SUCCESS;
+    respond(&mut buffer, clock.offset, &[1, 250_000_000]);
+    respond(&mut buffer, pitch.offset, &[4096]);
+    assert_eq!(buffer.status(), Ok(()));
+    assert_eq!(buffer.get(&clock), Ok(250_000_000));
+    assert_eq!(buffer.get(&pitch), Ok(4096));
+    assert_eq!(buffer.get(&mac), Err(Error::Unhandled));
+
+    respond(&mut buffer, mac.offset, &[0x44332211, 0x6655]);
+    assert_eq!(buffer.get(&mac), Ok([0x11, 0x22, 0x33, 0x44, 0x55, 0x66]));
+}
+
+#[test]
+fn full() {
+    let mut buffer = PropertyBuffer::new();
+    // Each tag takes 5 words; the header takes 2 and the end tag 1.
+    for _ in 0..(BUFFER_WORDS - 3) / 5 {
+        buffer.push(&GetClockRate(Clock::Core)).unwrap();
+    }
+    assert_eq!(buffer.push(&GetClockRate(Clock::Core)).unwrap_err(), Error::BufferFull);
+}
@@ -0,0 +1,443 @@
+use core::fmt;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, WriteVolatile, Reserved};
+
+use timer;
+use common::IO_BASE;
+use gpio::{Alt, Gpio, Function};
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the PL011 `UART0` registers.
+const UART_REG_BASE: usize = IO_BASE + 0x201000;
+
+/// The UART reference clock the firmware configures by default, in Hz.
+pub const DEFAULT_CLOCK: u32 = 48_000_000;
+
+/// Bit fields of the `FR` register.
+#[repr(u32)]
+enum Flag {
+    Busy = 1 << 3,
+    RxEmpty = 1 << 4,
+    TxFull = 1 << 5,
+}
+
+/// Bit fields of the `LCRH` register.
+#[repr(u32)]
+enum LineControl {
+    FifoEnable = 1 << 4,
+    WordLength8 = 0b11 << 5,
+}
+
+/// Bit fields of the `DMACR` register.
+#[repr(u32)]
+enum DmaControl {
+    RxEnable = 1 << 0,
+    TxEnable = 1 << 1,
+}
+
+/// Bit fields of the `CR` register.
+#[repr(u32)]
+enum Control {
+    Enable = 1 << 0,
+    TxEnable = 1 << 8,
+    RxEnable = 1 << 9,
+    RtsEnable = 1 << 14,
+    CtsEnable = 1 << 15,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    DR: Volatile<u32>,
+    RSRECR: Volatile<u32>,
+    __r0: [Reserved<u32>; 4],
+    FR: ReadVolatile<u32>,
+    __r1: Reserved<u32>,
+    ILPR: Volatile<u32>,
+    IBRD: Volatile<u32>,
+    FBRD: Volatile<u32>,
+    LCRH: Volatile<u32>,
+    CR: Volatile<u32>,
+    IFLS: Volatile<u32>,
+    IMSC: Volatile<u32>,
+    RIS: ReadVolatile<u32>,
+    MIS: ReadVolatile<u32>,
+    ICR: WriteVolatile<u32>,
+    DMACR: Volatile<u32>,
+}
+
+/// FIFO fill level at which the receive or transmit interrupt triggers.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FifoLevel {
+    OneEighth = 0b000,
+    OneQuarter = 0b001,
+    OneHalf = 0b010,
+    ThreeQuarters = 0b011,
+    SevenEighths = 0b100,
+}
+
+/// A PL011 interrupt source, as its bit in the mask and status registers.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interrupt {
+    /// The receive FIFO reached its trigger level.
+    Receive = 1 << 4,
+    /// The transmit FIFO dropped to its trigger level.
+    Transmit = 1 << 5,
+    /// The receive FIFO is non-empty and no data arrived for 32 bit periods.
+    ReceiveTimeout = 1 << 6,
+    Framing = 1 << 7,
+    Parity = 1 << 8,
+    Break = 1 << 9,
+    Overrun = 1 << 10,
+}
+
+/// Receive errors seen since they were last taken with
+/// `Uart::take_errors()`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LineErrors(u8);
+
+impl LineErrors {
+    const FRAMING: u8 = 1 << 0;
+    const PARITY: u8 = 1 << 1;
+    const BREAK: u8 = 1 << 2;
+    const OVERRUN: u8 = 1 << 3;
+
+    /// A received character had no valid stop bit.
+    pub fn framing(&self) -> bool { self.0 & LineErrors::FRAMING != 0 }
+
+    /// A received character's parity didn't match.
+    pub fn parity(&self) -> bool { self.0 & LineErrors::PARITY != 0 }
+
+    /// The receive line was held low for longer than a full character.
+    pub fn brk(&self) -> bool { self.0 & LineErrors::BREAK != 0 }
+
+    /// A character arrived while the receive FIFO was full and was lost.
+    pub fn overrun(&self) -> bool { self.0 & LineErrors::OVERRUN != 0 }
+
+    /// Returns `true` if no error occurred.
+    pub fn is_empty(&self) -> bool { self.0 == 0 }
+}
+
+/// Configuration for the PL011 UART.
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Frequency of the UART reference clock, in Hz.
+    pub clock: u32,
+    /// Requested baud rate.
+    pub baud_rate: u32,
+    /// Receive FIFO level that triggers `Interrupt::Receive`.
+    pub rx_trigger: FifoLevel,
+    /// Transmit FIFO level that triggers `Interrupt::Transmit`.
+    pub tx_trigger: FifoLevel,
+    /// Enables CTS/RTS hardware flow control on GPIO pins 16 and 17.
+    pub flow_control: bool,
+}
+
+impl Default for Config {
+    /// 115200 baud with the firmware's default reference clock, half-full
+    /// FIFO triggers and no flow control.
+    fn default() -> Config {
+        Config {
+            clock: DEFAULT_CLOCK,
+            baud_rate: 115200,
+            rx_trigger: FifoLevel::OneHalf,
+            tx_trigger: FifoLevel::OneHalf,
+            flow_control: false,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the integer and fractional baud rate divisors for this
+    /// configuration, to be written to `IBRD` and `FBRD`.
+    ///
+    /// The divisor is `clock / (16 * baud_rate)`, with the fraction expressed
+    /// in 64ths and rounded to the nearest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baud rate is `0` or the divisor falls outside the range
+    /// `IBRD` and `FBRD` can hold, from 1 to 65535.
+    pub fn divisors(&self) -> (u32, u32) {
+        let baud_rate = self.baud_rate as u64;
+        let div64 = (self.clock as u64 * 4 + baud_rate / 2) / baud_rate.max(1);
+        if self.baud_rate == 0 || div64 < 1 << 6 || div64 > 0xFFFF << 6 {
+            panic!("pl011::Config: baud rate {} unreachable from a {} Hz clock",
+                   self.baud_rate, self.clock);
+        }
+
+        ((div64 >> 6) as u32, (div64 & 0x3F) as u32)
+    }
+}
+
+/// The Raspberry Pi's full PL011 UART, `UART0`.
+///
+/// On the Raspberry Pi 3 this UART is connected to the Bluetooth module by
+/// default; `dtoverlay=pi3-miniuart-bt` in `config.txt` routes it to GPIO pins
+/// 14 and 15 instead.
+pub struct Uart {
+    registers: &'static mut Registers,
+    timeout: Option<u32>,
+    errors: LineErrors,
+    /// GPIO pins 14 and 15, then 16 and 17 with flow control, returned when
+    /// the UART is dropped.
+    _pins: [Option<Gpio<Alt>>; 4],
+}
+
+impl Uart {
+    /// Initializes the UART with the default configuration. See
+    /// `with_config()`.
+    pub fn new() -> Uart {
+        Uart::with_config(Config::default())
+    }
+
+    /// Initializes the UART by disabling it, setting GPIO pins 14 and 15 (and
+    /// 16 and 17 if flow control is requested) to their UART alternative
+    /// functions, programming the baud rate divisors, 8-bit data size and FIFO
+    /// trigger levels from `config`, and finally enabling the UART transmitter
+    /// and receiver. All UART interrupts start masked.
+    ///
+    /// By default, reads will never time out. To set a read timeout, use
+    /// `set_read_timeout()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the GPIO pins are already in use, for instance by the
+    /// mini UART. The pins are returned when the `Uart` is dropped.
+    pub fn with_config(config: Config) -> Uart {
+        // Claim the pins first, so a UART in use isn't disturbed on failure.
+        let (txd, rxd) = (Gpio::new(14), Gpio::new(15));
+        let flow = if config.flow_control {
+            Some([Gpio::new(16), Gpio::new(17)])
+        } else {
+            None
+        };
+        let registers = unsafe { &mut *(UART_REG_BASE as *mut Registers) };
+
+        // Disable the UART and let any pending transmission finish before
+        // touching its configuration. Clearing FEN flushes the FIFOs.
+        registers.CR.write(0);
+        while registers.FR.has_mask(Flag::Busy as u32) { }
+        registers.LCRH.write(0);
+
+        let mut pins = [Some(txd.into_alt(Function::Alt0)), Some(rxd.into_alt(Function::Alt0)), None, None];
+        if let Some([cts, rts]) = flow {
+            pins[2] = Some(cts.into_alt(Function::Alt3));
+            pins[3] = Some(rts.into_alt(Function::Alt3));
+        }
+
+        registers.IMSC.write(0);
+        registers.ICR.write(0x7FF);
+
+        let (integer, fraction) = config.divisors();
+        registers.IBRD.write(integer);
+        registers.FBRD.write(fraction);
+
+        // LCRH must be written after the divisors for them to take effect.
+        registers.LCRH.write(LineControl::WordLength8 as u32 | LineControl::FifoEnable as u32);
+        registers.IFLS.write(((config.rx_trigger as u32) << 3) | config.tx_trigger as u32);
+
+        let mut control = Control::Enable as u32 | Control::TxEnable as u32 | Control::RxEnable as u32;
+        if config.flow_control {
+            control |= Control::RtsEnable as u32 | Control::CtsEnable as u32;
+        }
+        registers.CR.write(control);
+
+        Uart {
+            registers: registers,
+            timeout: None,
+            errors: LineErrors::default(),
+            _pins: pins,
+        }
+    }
+
+    /// Returns a handle to the UART without reconfiguring it, for use from
+    /// an interrupt handler. The handle doesn't own the GPIO pins.
+    ///
+    /// # Safety
+    ///
+    /// The UART must already have been initialized with `new()` or
+    /// `with_config()`, and the caller must not race with another handle's
+    /// use of the same registers.
+    pub unsafe fn from_initialized() -> Uart {
+        Uart {
+            registers: &mut *(UART_REG_BASE as *mut Registers),
+            timeout: None,
+            errors: LineErrors::default(),
+            _pins: [None, None, None, None],
+        }
+    }
+
+    /// Set the read timeout to `milliseconds` milliseconds.
+    pub fn set_read_timeout(&mut self, milliseconds: u32) {
+        self.timeout = Some(milliseconds)
+    }
+
+    /// Write the byte `byte`. This method blocks until there is space available
+    /// in the output FIFO.
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.registers.FR.has_mask(Flag::TxFull as u32) { }
+        self.registers.DR.write(byte as u32);
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read. If this
+    /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately. This method does not block.
+    pub fn has_byte(&self) -> bool {
+        !self.registers.FR.has_mask(Flag::RxEmpty as u32)
+    }
+
+    /// Blocks until there is a byte ready to read. If a read timeout is set,
+    /// this method blocks for at most that amount of time. Otherwise, this
+    /// method blocks indefinitely until there is a byte to read.
+    ///
+    /// Returns `Ok(())` if a byte is ready to read. Returns `Err(())` if the
+    /// timeout expired while waiting for a byte to be ready.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        let deadline = self.timeout.map(|ms| timer::current_time() + (ms as u64) * 1000);
+        loop {
+            if self.has_byte() {
+                return Ok(());
+            }
+
+            if let Some(deadline) = deadline {
+                if timer::current_time() > deadline {
+                    return Err(());
+                }
+            }
+        }
+    }
+
+    /// Reads a byte. Blocks indefinitely until a byte is ready to be read.
+    ///
+    /// Errors flagged for the byte are recorded and can be retrieved with
+    /// `take_errors()`.
+    pub fn read_byte(&mut self) -> u8 {
+        while !self.has_byte() { }
+        let data = self.registers.DR.read();
+        self.errors.0 |= ((data >> 8) & 0xF) as u8;
+        data as u8
+    }
+
+    /// Returns the receive errors seen since the last call and clears them.
+    pub fn take_errors(&mut self) -> LineErrors {
+        self.registers.RSRECR.write(0);
+        let errors = self.errors;
+        self.errors = LineErrors::default();
+        errors
+    }
+
+    /// Unmasks the interrupt `int`. The UART interrupt must also be enabled
+    /// in the interrupt controller for it to reach the CPU.
+    pub fn enable_interrupt(&mut self, int: Interrupt) {
+        self.registers.IMSC.or_mask(int as u32);
+    }
+
+    /// Masks the interrupt `int`.
+    pub fn disable_interrupt(&mut self, int: Interrupt) {
+        self.registers.IMSC.and_mask(!(int as u32));
+    }
+
+    /// Returns `true` if `int` is unmasked and asserted.
+    pub fn is_interrupt_pending(&self, int: Interrupt) -> bool {
+        self.registers.MIS.has_mask(int as u32)
+    }
+
+    /// Clears the interrupt `int`.
+    pub fn clear_interrupt(&mut self, int: Interrupt) {
+        self.registers.ICR.write(int as u32);
+    }
+
+    /// Enables or disables the receive interrupts, `Interrupt::Receive` and
+    /// `Interrupt::ReceiveTimeout`. Together they are asserted while the
+    /// receive FIFO holds any bytes: the timeout covers bytes below the
+    /// trigger level. Both are cleared by draining the FIFO.
+    pub fn set_rx_interrupt(&mut self, enabled: bool) {
+        let mask = Interrupt::Receive as u32 | Interrupt::ReceiveTimeout as u32;
+        if enabled {
+            self.registers.IMSC.or_mask(mask);
+        } else {
+            self.registers.IMSC.and_mask(!mask);
+        }
+    }
+
+    /// Returns `true` if bytes were dropped because the receive FIFO was full
+    /// since the last call, and clears the condition.
+    pub fn rx_overrun(&mut self) -> bool {
+        let overrun = self.registers.RIS.has_mask(Interrupt::Overrun as u32);
+        self.clear_interrupt(Interrupt::Overrun);
+        overrun
+    }
+
+    /// Enables or disables DMA requests for the receive and transmit FIFOs.
+    /// See `dma::Peripheral::UartRx` and `dma::Peripheral::UartTx`.
+    pub fn set_dma(&mut self, rx: bool, tx: bool) {
+        let mut control = 0;
+        if rx {
+            control |= DmaControl::RxEnable as u32;
+        }
+        if tx {
+            control |= DmaControl::TxEnable as u32;
+        }
+        self.registers.DMACR.write(control);
+    }
+
+    /// Blocks until every queued byte has been transmitted.
+    pub fn flush(&mut self) {
+        while self.registers.FR.has_mask(Flag::Busy as u32) { }
+    }
+}
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+mod uart_io {
+    use std::io;
+    use super::Uart;
+
+    impl io::Read for Uart {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.wait_for_byte() {
+                Ok(()) => {
+                    let mut index = 0;
+                    while self.has_byte() && index < buf.len() {
+                        buf[index] = self.read_byte();
+                        index += 1;
+                    }
+                    Ok(index)
+                },
+                Err(()) => {
+                    Err(io::Error::new(io::ErrorKind::TimedOut, "reading UART timed out"))
+                }
+            }
+        }
+    }
+
+    impl io::Write for Uart {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for byte in buf {
+                self.write_byte(*byte);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Uart::flush(self);
+            Ok(())
+        }
+    }
+}
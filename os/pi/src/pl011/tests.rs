@@ -0,0 +1,38 @@
+use super::Config;
+
+fn config(baud_rate: u32) -> Config {
+    Config { clock: 48_000_000, baud_rate: baud_rate, ..Config::default() }
+}
+
+#[test]
+fn divisors() {
+    // 48MHz / (16 * 115200) = 26.042, and 0.042 * 64 rounds to 3.
+    assert_eq!(config(115200).divisors(), (26, 3));
+    assert_eq!(config(9600).divisors(), (312, 32));
+    assert_eq!(config(921600).divisors(), (3, 16));
+}
+
+#[test]
+fn limits() {
+    // `IBRD` ranges from 1 to 65535.
+    assert_eq!(config(3_000_000).divisors(), (1, 0));
+    assert_eq!(config(46).divisors(), (65217, 25));
+}
+
+#[test]
+#[should_panic(expected = "unreachable")]
+fn too_fast() {
+    config(3_100_000).divisors();
+}
+
+#[test]
+#[should_panic(expected = "unreachable")]
+fn too_slow() {
+    config(45).divisors();
+}
+
+#[test]
+#[should_panic(expected = "unreachable")]
+fn zero_baud_rate() {
+    config(0).divisors();
+}
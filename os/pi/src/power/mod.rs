@@ -0,0 +1,163 @@
+#[cfg(target_arch = "aarch64")]
+use core::arch::asm;
+
+use volatile::prelude::*;
+use volatile::{Volatile, Reserved};
+
+use common::IO_BASE;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the power management (`PM`) registers.
+const PM_BASE: usize = IO_BASE + 0x100000;
+
+/// Every write to a `PM` register must carry this in its top byte.
+const PASSWORD: u32 = 0x5A << 24;
+
+/// Bit fields of the `RSTC` register.
+#[repr(u32)]
+enum ResetControl {
+    /// What happens when the watchdog expires.
+    Config = 0b11 << 4,
+    /// Reset the whole chip when the watchdog expires.
+    FullReset = 0b10 << 4,
+    /// Disarms the watchdog.
+    Stop = 0x102,
+}
+
+/// The firmware reads the partition to boot from the even bits 0 to 10 of
+/// `RSTS`.
+const RSTS_PARTITION_MASK: u32 = 0x555;
+
+/// Mask of the countdown in `WDOG`.
+const WDOG_TIME_MASK: u32 = 0xFFFFF;
+
+/// The watchdog counts down 65536 ticks per second.
+const TICKS_PER_SECOND: u64 = 65536;
+
+/// The longest watchdog timeout, in milliseconds: just under 16 seconds.
+pub const MAX_TIMEOUT_MS: u32 = (WDOG_TIME_MASK as u64 * 1000 / TICKS_PER_SECOND) as u32;
+
+/// Asking the firmware to boot from this partition makes it halt instead.
+pub const HALT_PARTITION: u8 = 63;
+
+/// Ticks a reset is delayed by, letting the write to `RSTC` land first.
+const RESET_TICKS: u32 = 10;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    __r0: [Reserved<u32>; 7],
+    RSTC: Volatile<u32>,
+    RSTS: Volatile<u32>,
+    WDOG: Volatile<u32>,
+}
+
+fn registers() -> &'static mut Registers {
+    unsafe { &mut *(PM_BASE as *mut Registers) }
+}
+
+/// Returns `partition` spread over the even bits of `RSTS`.
+fn partition_bits(partition: u8) -> u32 {
+    (0..6).fold(0, |bits, i| bits | ((partition as u32 >> i) & 1) << (2 * i))
+}
+
+/// Returns the value to write to `RSTS` to have the firmware boot
+/// `partition`, keeping the other bits of `status`, the register's current
+/// value.
+fn rsts_for(status: u32, partition: u8) -> u32 {
+    let status = status & !(RSTS_PARTITION_MASK | 0xFF << 24);
+    PASSWORD | status | partition_bits(partition)
+}
+
+/// Arms the watchdog to reset the chip after `ticks` ticks.
+fn arm(registers: &mut Registers, ticks: u32) {
+    let control = registers.RSTC.read() & !(ResetControl::Config as u32 | 0xFF << 24);
+    registers.WDOG.write(PASSWORD | (ticks & WDOG_TIME_MASK));
+    registers.RSTC.write(PASSWORD | control | ResetControl::FullReset as u32);
+}
+
+/// The PM watchdog, which resets the board unless it's kicked before its
+/// timeout runs out.
+///
+/// Dropping a `Watchdog` leaves it running; call `stop()` to disarm it.
+/// Nothing stops two `Watchdog`s from existing; they share the one timer.
+pub struct Watchdog {
+    registers: &'static mut Registers,
+    ticks: u32,
+}
+
+impl Watchdog {
+    /// Arms the watchdog to reset the board after `timeout_ms` milliseconds
+    /// without a kick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout_ms` is `0` or longer than `MAX_TIMEOUT_MS`.
+    pub fn start(timeout_ms: u32) -> Watchdog {
+        assert!(timeout_ms > 0 && timeout_ms <= MAX_TIMEOUT_MS,
+                "Watchdog::start(): timeout of {}ms out of range", timeout_ms);
+
+        let ticks = (timeout_ms as u64 * TICKS_PER_SECOND / 1000) as u32;
+        let mut watchdog = Watchdog { registers: registers(), ticks };
+        watchdog.kick();
+        watchdog
+    }
+
+    /// Restarts the countdown from the full timeout.
+    pub fn kick(&mut self) {
+        arm(self.registers, self.ticks);
+    }
+
+    /// Returns the time left before the board resets, in milliseconds.
+    pub fn remaining_ms(&self) -> u32 {
+        let ticks = self.registers.WDOG.read() & WDOG_TIME_MASK;
+        (ticks as u64 * 1000 / TICKS_PER_SECOND) as u32
+    }
+
+    /// Disarms the watchdog.
+    pub fn stop(self) {
+        self.registers.RSTC.write(PASSWORD | ResetControl::Stop as u32);
+    }
+}
+
+/// Resets the board, which boots normally.
+pub fn reset() -> ! {
+    reboot_to(0)
+}
+
+/// Resets the board and has the firmware boot from SD card partition
+/// `partition` instead of the first FAT partition. Partition `0` boots
+/// normally, which brings up the bootloader again.
+///
+/// # Panics
+///
+/// Panics if `partition` is higher than 63.
+pub fn reboot_to(partition: u8) -> ! {
+    assert!(partition <= HALT_PARTITION, "power::reboot_to(): partition {} out of range", partition);
+
+    let registers = registers();
+    let status = registers.RSTS.read();
+    registers.RSTS.write(rsts_for(status, partition));
+    arm(registers, RESET_TICKS);
+    loop {
+        wait_for_event();
+    }
+}
+
+/// Idles the core until an event arrives. Other architectures, such as the
+/// host running unit tests, only hint that they're spinning.
+fn wait_for_event() {
+    #[cfg(target_arch = "aarch64")]
+    unsafe { asm!("wfe", options(nomem, nostack)) }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    ::core::hint::spin_loop();
+}
+
+/// Halts the board: the firmware comes back up from the reset, powers
+/// everything down it can and waits for power to be cycled.
+pub fn halt() -> ! {
+    reboot_to(HALT_PARTITION)
+}
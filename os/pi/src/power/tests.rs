@@ -0,0 +1,19 @@
+use super::{partition_bits, rsts_for, HALT_PARTITION};
+
+#[test]
+fn partitions() {
+    assert_eq!(partition_bits(0), 0);
+    assert_eq!(partition_bits(1), 1);
+    assert_eq!(partition_bits(2), 4);
+    assert_eq!(partition_bits(5), 0b10001);
+    assert_eq!(partition_bits(HALT_PARTITION), 0x555);
+}
+
+#[test]
+fn rsts() {
+    // Bits outside the partition are kept, except the password byte.
+    assert_eq!(rsts_for(0xFFFF_FFFF, HALT_PARTITION), 0x5AFF_FFFF);
+    assert_eq!(rsts_for(0xFFFF_FFFF, 0), 0x5AFF_FAAA);
+    assert_eq!(rsts_for(0x1234_0555, 2), 0x5A34_0004);
+    assert_eq!(rsts_for(0, 1), 0x5A00_0001);
+}
@@ -0,0 +1,315 @@
+use volatile::prelude::*;
+use volatile::{Volatile, Reserved};
+
+use clock::{Clock, ClockId, Source};
+use common::IO_BASE;
+use gpio::{Alt, Gpio, Function};
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the `PWM` registers.
+const PWM_REG_BASE: usize = IO_BASE + 0x20C000;
+
+/// Bit fields of the `CTL` register for channel 1. Channel 2's are 8 bits
+/// higher, except `ClearFifo`, which is shared.
+#[repr(u32)]
+enum Control {
+    Enable = 1 << 0,
+    Serializer = 1 << 1,
+    RepeatLast = 1 << 2,
+    Polarity = 1 << 4,
+    UseFifo = 1 << 5,
+    ClearFifo = 1 << 6,
+    MarkSpace = 1 << 7,
+}
+
+/// Bit fields of the `STA` register.
+#[repr(u32)]
+enum Status {
+    FifoFull = 1 << 0,
+    FifoEmpty = 1 << 1,
+    WriteError = 1 << 2,
+    ReadError = 1 << 3,
+    BusError = 1 << 8,
+}
+
+/// Bit fields of the `DMAC` register.
+#[repr(u32)]
+enum DmaControl {
+    /// Raise DREQ below 7 words, and panic below 7 words.
+    Thresholds = 7 << 8 | 7,
+    Enable = 1 << 31,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTL: Volatile<u32>,
+    STA: Volatile<u32>,
+    DMAC: Volatile<u32>,
+    __r0: Reserved<u32>,
+    RNG1: Volatile<u32>,
+    DAT1: Volatile<u32>,
+    FIF1: Volatile<u32>,
+    __r1: Reserved<u32>,
+    RNG2: Volatile<u32>,
+    DAT2: Volatile<u32>,
+}
+
+/// A PWM channel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    One,
+    Two,
+}
+
+impl Channel {
+    /// Returns the shift of this channel's bits in `CTL`.
+    fn shift(self) -> u32 {
+        match self {
+            Channel::One => 0,
+            Channel::Two => 8,
+        }
+    }
+}
+
+/// A GPIO pin a PWM channel can drive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pin {
+    Gpio12,
+    Gpio13,
+    Gpio18,
+    Gpio19,
+    /// The left channel of the headphone jack.
+    Gpio40,
+    /// The right channel of the headphone jack.
+    Gpio41,
+}
+
+impl Pin {
+    /// Returns the channel driving this pin.
+    pub fn channel(self) -> Channel {
+        match self {
+            Pin::Gpio12 | Pin::Gpio18 | Pin::Gpio40 => Channel::One,
+            Pin::Gpio13 | Pin::Gpio19 | Pin::Gpio41 => Channel::Two,
+        }
+    }
+
+    /// Returns the pin number and the alternative function routing it to the
+    /// PWM.
+    fn function(self) -> (u8, Function) {
+        match self {
+            Pin::Gpio12 => (12, Function::Alt0),
+            Pin::Gpio13 => (13, Function::Alt0),
+            Pin::Gpio18 => (18, Function::Alt5),
+            Pin::Gpio19 => (19, Function::Alt5),
+            Pin::Gpio40 => (40, Function::Alt0),
+            Pin::Gpio41 => (41, Function::Alt0),
+        }
+    }
+}
+
+/// How a channel turns its data and range into an output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Spreads `data` high cycles out as evenly as possible over every
+    /// `range` cycles. Best for filtering into an analog level, such as
+    /// audio.
+    Balanced,
+    /// Outputs `data` high cycles followed by `range - data` low cycles.
+    /// Best for dimming LEDs and driving servos.
+    MarkSpace,
+    /// Shifts out the `range` most significant bits of each data word.
+    Serializer,
+}
+
+/// Configuration for a PWM channel.
+#[derive(Debug, Copy, Clone)]
+pub struct ChannelConfig {
+    pub mode: Mode,
+    /// Length of a period, in PWM clock cycles.
+    pub range: u32,
+    /// Takes data from the FIFO instead of the channel's data register.
+    pub use_fifo: bool,
+    /// Inverts the output.
+    pub inverted: bool,
+}
+
+impl Default for ChannelConfig {
+    /// Mark-space mode with a range of 1024, data from the data register and
+    /// no inversion.
+    fn default() -> ChannelConfig {
+        ChannelConfig {
+            mode: Mode::MarkSpace,
+            range: 1024,
+            use_fifo: false,
+            inverted: false,
+        }
+    }
+}
+
+/// Errors the PWM flagged since they were last taken.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Errors {
+    /// A word was written to the FIFO while it was full.
+    pub fifo_write: bool,
+    /// A word was read from the FIFO while it was empty.
+    pub fifo_read: bool,
+    /// A register was written while the APB bus was busy.
+    pub bus: bool,
+}
+
+/// The PWM controller, with its two channels.
+pub struct Pwm {
+    registers: &'static mut Registers,
+    clock: Clock,
+    /// The pins routed to the channels, indexed by `Pin`. They are returned
+    /// when the `Pwm` is dropped.
+    routes: [Option<Gpio<Alt>>; 6],
+}
+
+impl Pwm {
+    /// Stops both channels and restarts the PWM clock at the rate closest to
+    /// `hz` that `source` can be divided to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hz` is unreachable from `source`. See
+    /// `Clock::set_frequency()`.
+    pub fn new(source: Source, hz: u32) -> Pwm {
+        let mut pwm = unsafe { Pwm::at(PWM_REG_BASE) };
+        pwm.registers.CTL.write(0);
+        pwm.set_clock(source, hz);
+        pwm
+    }
+
+    /// Returns a handle to PWM registers at `base` without stopping the
+    /// channels or touching the clock. `set_clock()` still programs the real
+    /// PWM clock.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to a `'static` PWM register block.
+    pub(crate) unsafe fn at(base: usize) -> Pwm {
+        Pwm {
+            registers: &mut *(base as *mut Registers),
+            clock: Clock::new(ClockId::Pwm),
+            routes: [None, None, None, None, None, None],
+        }
+    }
+
+    /// Restarts the PWM clock at the rate closest to `hz` that `source` can be
+    /// divided to, returning the rate set. See `Clock::set_frequency()`.
+    pub fn set_clock(&mut self, source: Source, hz: u32) -> u32 {
+        // The channels must be idle while their clock changes.
+        let control = self.registers.CTL.read();
+        self.registers.CTL.write(0);
+        let rate = self.clock.set_frequency(source, hz);
+        self.registers.CTL.write(control);
+        rate
+    }
+
+    /// Routes `pin` to its channel. Routing a pin twice has no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the GPIO pin is already in use elsewhere. The pin is
+    /// returned when the `Pwm` is dropped.
+    pub fn route(&mut self, pin: Pin) {
+        let route = &mut self.routes[pin as usize];
+        if route.is_none() {
+            let (number, function) = pin.function();
+            *route = Some(Gpio::new(number).into_alt(function));
+        }
+    }
+
+    /// Configures `channel` from `config` and enables it.
+    pub fn enable(&mut self, channel: Channel, config: ChannelConfig) {
+        self.disable(channel);
+        self.set_range(channel, config.range);
+
+        let mut bits = Control::Enable as u32;
+        match config.mode {
+            Mode::Balanced => {}
+            Mode::MarkSpace => bits |= Control::MarkSpace as u32,
+            Mode::Serializer => bits |= Control::Serializer as u32,
+        }
+        if config.use_fifo {
+            bits |= Control::UseFifo as u32 | Control::RepeatLast as u32;
+        }
+        if config.inverted {
+            bits |= Control::Polarity as u32;
+        }
+
+        self.registers.CTL.or_mask(bits << channel.shift());
+    }
+
+    /// Disables `channel`, leaving its output low.
+    pub fn disable(&mut self, channel: Channel) {
+        self.registers.CTL.and_mask(!(0xBF << channel.shift()));
+    }
+
+    /// Sets the length of `channel`'s period, in PWM clock cycles.
+    pub fn set_range(&mut self, channel: Channel, range: u32) {
+        match channel {
+            Channel::One => self.registers.RNG1.write(range),
+            Channel::Two => self.registers.RNG2.write(range),
+        }
+    }
+
+    /// Sets `channel`'s data: the number of high cycles per period, or the
+    /// bits to shift out in serializer mode.
+    pub fn set_data(&mut self, channel: Channel, data: u32) {
+        match channel {
+            Channel::One => self.registers.DAT1.write(data),
+            Channel::Two => self.registers.DAT2.write(data),
+        }
+    }
+
+    /// Returns `true` if the FIFO can't take another word.
+    pub fn is_fifo_full(&self) -> bool {
+        self.registers.STA.has_mask(Status::FifoFull as u32)
+    }
+
+    /// Returns `true` if the FIFO has run dry.
+    pub fn is_fifo_empty(&self) -> bool {
+        self.registers.STA.has_mask(Status::FifoEmpty as u32)
+    }
+
+    /// Queues `data` in the FIFO, blocking while it's full. With both
+    /// channels using the FIFO, words alternate between them, starting with
+    /// channel 1.
+    pub fn write_fifo(&mut self, data: u32) {
+        while self.is_fifo_full() { }
+        self.registers.FIF1.write(data);
+    }
+
+    /// Discards every word in the FIFO.
+    pub fn clear_fifo(&mut self) {
+        self.registers.CTL.or_mask(Control::ClearFifo as u32);
+    }
+
+    /// Enables or disables DMA requests to fill the FIFO. See
+    /// `dma::Peripheral::Pwm`.
+    pub fn set_dma(&mut self, enabled: bool) {
+        let mut control = DmaControl::Thresholds as u32;
+        if enabled {
+            control |= DmaControl::Enable as u32;
+        }
+        self.registers.DMAC.write(control);
+    }
+
+    /// Returns the errors flagged since the last call and clears them.
+    pub fn take_errors(&mut self) -> Errors {
+        let mask = Status::WriteError as u32 | Status::ReadError as u32 | Status::BusError as u32;
+        let status = self.registers.STA.read();
+        self.registers.STA.write(status & mask);
+
+        Errors {
+            fifo_write: status & Status::WriteError as u32 != 0,
+            fifo_read: status & Status::ReadError as u32 != 0,
+            bus: status & Status::BusError as u32 != 0,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use test_util::MockRegs;
+
+use super::{Channel, ChannelConfig, Mode, Pwm};
+
+/// Word offsets of the PWM registers used below.
+const CTL: usize = 0;
+const STA: usize = 1;
+const RNG1: usize = 4;
+const DAT1: usize = 5;
+const RNG2: usize = 8;
+const DAT2: usize = 9;
+
+/// Channel 1's `CTL` bits. Channel 2's are 8 bits higher.
+const PWEN: u32 = 1 << 0;
+const MODE: u32 = 1 << 1;
+const RPTL: u32 = 1 << 2;
+const POLA: u32 = 1 << 4;
+const USEF: u32 = 1 << 5;
+const CLRF: u32 = 1 << 6;
+const MSEN: u32 = 1 << 7;
+
+/// `STA` bits.
+const EMPT: u32 = 1 << 1;
+const WERR: u32 = 1 << 2;
+const RERR: u32 = 1 << 3;
+const BERR: u32 = 1 << 8;
+
+#[test]
+fn enable() {
+    let regs = MockRegs::<10>::new();
+    let mut pwm = unsafe { Pwm::at(regs.base()) };
+
+    pwm.enable(Channel::One, ChannelConfig::default());
+    assert_eq!(regs[CTL], PWEN | MSEN);
+    assert_eq!(regs[RNG1], 1024);
+
+    let config = ChannelConfig { mode: Mode::Serializer, range: 32, use_fifo: true, inverted: true };
+    pwm.enable(Channel::Two, config);
+    assert_eq!(regs[CTL], PWEN | MSEN | (PWEN | MODE | USEF | RPTL | POLA) << 8);
+    assert_eq!(regs[RNG2], 32);
+    assert_eq!(regs[RNG1], 1024);
+
+    // Re-enabling replaces the channel's previous configuration.
+    pwm.enable(Channel::One, ChannelConfig { mode: Mode::Balanced, ..ChannelConfig::default() });
+    assert_eq!(regs[CTL] & 0xFF, PWEN);
+}
+
+#[test]
+fn disable() {
+    let mut regs = MockRegs::<10>::new();
+    regs[CTL] = 0xFFFF;
+    let mut pwm = unsafe { Pwm::at(regs.base()) };
+
+    // The shared FIFO clear bit and the other channel are left alone.
+    pwm.disable(Channel::One);
+    assert_eq!(regs[CTL], 0xFF00 | CLRF);
+
+    pwm.disable(Channel::Two);
+    assert_eq!(regs[CTL], CLRF << 8 | CLRF);
+}
+
+#[test]
+fn data() {
+    let regs = MockRegs::<10>::new();
+    let mut pwm = unsafe { Pwm::at(regs.base()) };
+
+    pwm.set_data(Channel::One, 100);
+    pwm.set_data(Channel::Two, 200);
+    assert_eq!(regs[DAT1], 100);
+    assert_eq!(regs[DAT2], 200);
+}
+
+#[test]
+fn take_errors() {
+    let mut regs = MockRegs::<10>::new();
+    regs[STA] = EMPT | WERR | BERR;
+    let mut pwm = unsafe { Pwm::at(regs.base()) };
+
+    let errors = pwm.take_errors();
+    assert!(errors.fifo_write);
+    assert!(!errors.fifo_read);
+    assert!(errors.bus);
+
+    // `STA` is write-one-to-clear: only the error flags seen are written.
+    assert_eq!(regs[STA], WERR | BERR);
+
+    regs[STA] = RERR;
+    assert!(pwm.take_errors().fifo_read);
+    assert_eq!(regs[STA], RERR);
+}
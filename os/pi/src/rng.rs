@@ -0,0 +1,127 @@
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile};
+
+use common::IO_BASE;
+
+/// The base address for the `RNG` registers.
+const RNG_REG_BASE: usize = IO_BASE + 0x104000;
+
+/// Number of numbers the generator discards after it's enabled, while its
+/// entropy builds up.
+const WARMUP_COUNT: u32 = 0x40000;
+
+/// Bit fields of the `CTRL` register.
+#[repr(u32)]
+enum Control {
+    Enable = 1 << 0,
+}
+
+/// Shift of the count of words in the FIFO in `STATUS`.
+const STATUS_AVAILABLE_SHIFT: u32 = 24;
+
+/// Bit fields of the `INT_MASK` register.
+#[repr(u32)]
+enum InterruptMask {
+    Off = 1 << 0,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTRL: Volatile<u32>,
+    STATUS: Volatile<u32>,
+    DATA: ReadVolatile<u32>,
+    FF_THRESHOLD: Volatile<u32>,
+    INT_MASK: Volatile<u32>,
+}
+
+/// The hardware random number generator.
+///
+/// Nothing stops two `Rng`s from existing; they draw from the same FIFO, so
+/// no word is handed out twice.
+pub struct Rng {
+    registers: &'static mut Registers,
+}
+
+impl Rng {
+    /// Returns a handle to the generator, enabling it if it isn't already.
+    /// A freshly enabled generator warms up before its first number is
+    /// ready; reads block until then.
+    pub fn new() -> Rng {
+        let registers = unsafe { &mut *(RNG_REG_BASE as *mut Registers) };
+        if !registers.CTRL.has_mask(Control::Enable as u32) {
+            registers.INT_MASK.or_mask(InterruptMask::Off as u32);
+            registers.STATUS.write(WARMUP_COUNT);
+            registers.CTRL.write(Control::Enable as u32);
+        }
+
+        Rng { registers }
+    }
+
+    /// Returns the number of 32-bit words waiting in the FIFO. This is `0`
+    /// while the generator warms up.
+    pub fn available(&self) -> usize {
+        (self.registers.STATUS.read() >> STATUS_AVAILABLE_SHIFT) as usize
+    }
+
+    /// Returns `true` if a word can be read without blocking.
+    pub fn is_ready(&self) -> bool {
+        self.available() > 0
+    }
+
+    /// Returns the next word if one is waiting in the FIFO.
+    pub fn try_next_u32(&mut self) -> Option<u32> {
+        if self.is_ready() {
+            Some(self.registers.DATA.read())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the next random word, blocking until one is available.
+    pub fn next_u32(&mut self) -> u32 {
+        while !self.is_ready() { }
+        self.registers.DATA.read()
+    }
+
+    /// Returns a random 64-bit number made of the next two words.
+    pub fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        high << 32 | low
+    }
+
+    /// Fills `dest` with random bytes, blocking until enough are available.
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let word = self.next_u32();
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = (word >> (8 * i)) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl ::rand_core::RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        Rng::next_u32(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        Rng::next_u64(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        Rng::fill_bytes(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand_core::Error> {
+        Rng::fill_bytes(self, dest);
+        Ok(())
+    }
+}
+
+/// The generator is a hardware entropy source, which is suitable for keys.
+#[cfg(feature = "rand_core")]
+impl ::rand_core::CryptoRng for Rng {}
@@ -0,0 +1,320 @@
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use common::IO_BASE;
+use dma::{self, ControlBlock, Peripheral};
+use gpio::{Alt, Gpio, Function};
+use uart::DEFAULT_CORE_CLOCK;
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the `SPI0` registers.
+const SPI_REG_BASE: usize = IO_BASE + 0x204000;
+
+/// Bit fields of the `CS` register.
+#[repr(u32)]
+enum Control {
+    ChipSelect = 0b11,
+    Cpha = 1 << 2,
+    Cpol = 1 << 3,
+    ClearTx = 1 << 4,
+    ClearRx = 1 << 5,
+    TransferActive = 1 << 7,
+    DmaEnable = 1 << 8,
+    AutoDeassert = 1 << 11,
+    Done = 1 << 16,
+    RxData = 1 << 17,
+    TxSpace = 1 << 18,
+}
+
+/// Shift of the per-line chip select polarity bits, `CSPOL0` to `CSPOL2`.
+const CS_POLARITY_SHIFT: u32 = 21;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CS: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    CLK: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    LTOH: Volatile<u32>,
+    DC: Volatile<u32>,
+}
+
+/// Clock polarity and phase.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Clock idles low, data is sampled on the rising edge.
+    Mode0,
+    /// Clock idles low, data is sampled on the falling edge.
+    Mode1,
+    /// Clock idles high, data is sampled on the falling edge.
+    Mode2,
+    /// Clock idles high, data is sampled on the rising edge.
+    Mode3,
+}
+
+impl Mode {
+    /// Returns the `CPOL` and `CPHA` bits of `CS` for this mode.
+    fn bits(self) -> u32 {
+        match self {
+            Mode::Mode0 => 0,
+            Mode::Mode1 => Control::Cpha as u32,
+            Mode::Mode2 => Control::Cpol as u32,
+            Mode::Mode3 => Control::Cpol as u32 | Control::Cpha as u32,
+        }
+    }
+}
+
+/// A chip select line: `CE0` on GPIO pin 8 or `CE1` on GPIO pin 7.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChipSelect {
+    Zero = 0,
+    One = 1,
+}
+
+/// Configuration for SPI0.
+#[derive(Debug, Copy, Clone)]
+pub struct SpiConfig {
+    /// Frequency of the VPU core clock, in Hz. The SPI clock is derived from
+    /// it, so it must match the firmware's `core_freq`.
+    pub core_clock: u32,
+    /// Requested SPI clock frequency, in Hz.
+    pub frequency: u32,
+    /// Clock polarity and phase.
+    pub mode: Mode,
+    /// The line asserted during transfers.
+    pub chip_select: ChipSelect,
+    /// `true` if the chip select line is asserted high rather than low.
+    pub cs_active_high: bool,
+}
+
+impl Default for SpiConfig {
+    /// 1MHz in mode 0 on an active-low `CE0`, at the default core clock.
+    fn default() -> SpiConfig {
+        SpiConfig {
+            core_clock: DEFAULT_CORE_CLOCK,
+            frequency: 1_000_000,
+            mode: Mode::Mode0,
+            chip_select: ChipSelect::Zero,
+            cs_active_high: false,
+        }
+    }
+}
+
+impl SpiConfig {
+    /// Returns the value for `CLK` giving the fastest clock no higher than the
+    /// requested frequency, from `frequency = core_clock / divisor` with an
+    /// even divisor. The slowest clock, a divisor of 65536, is written as `0`.
+    pub fn divisor(&self) -> u16 {
+        let frequency = self.frequency.max(1) as u64;
+        let divisor = (self.core_clock as u64).div_ceil(frequency);
+        let divisor = (divisor + 1) & !1;
+        match divisor {
+            0..=2 => 2,
+            0x10000..=0xFFFFFFFF => 0,
+            _ => divisor as u16,
+        }
+    }
+
+    /// Returns the SPI clock frequency actually achieved by `divisor()`.
+    pub fn actual_frequency(&self) -> u32 {
+        match self.divisor() {
+            0 => self.core_clock / 0x10000,
+            divisor => self.core_clock / divisor as u32,
+        }
+    }
+}
+
+/// The Raspberry Pi's SPI0 controller, as bus master.
+pub struct Spi {
+    registers: &'static mut Registers,
+    /// GPIO pins 7 to 11, returned when the controller is dropped.
+    _pins: Option<[Gpio<Alt>; 5]>,
+}
+
+impl Spi {
+    /// Initializes SPI0 with the default configuration. See `with_config()`.
+    pub fn new() -> Spi {
+        Spi::with_config(SpiConfig::default())
+    }
+
+    /// Initializes SPI0 by setting GPIO pins 7 to 11 to alternative function
+    /// 0 (`CE1`, `CE0`, `MISO`, `MOSI` and `SCLK`), clearing both FIFOs, and
+    /// programming the clock divisor, mode and chip select from `config`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the GPIO pins are already in use. The pins are
+    /// returned when the `Spi` is dropped.
+    pub fn with_config(config: SpiConfig) -> Spi {
+        let pins = [7, 8, 9, 10, 11].map(|pin| Gpio::new(pin).into_alt(Function::Alt0));
+
+        let mut spi = unsafe { Spi::at(SPI_REG_BASE) };
+        spi._pins = Some(pins);
+        spi.configure(&config);
+        spi
+    }
+
+    /// Returns a handle to SPI registers at `base` without configuring them
+    /// or claiming GPIO pins. Transfers use whatever clock, mode and chip
+    /// select the block already holds.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to a `'static` SPI register block.
+    pub(crate) unsafe fn at(base: usize) -> Spi {
+        Spi {
+            registers: &mut *(base as *mut Registers),
+            _pins: None,
+        }
+    }
+
+    /// Clears both FIFOs and programs the clock divisor, mode and chip select
+    /// from `config`.
+    fn configure(&mut self, config: &SpiConfig) {
+        self.registers.CS.write(Control::ClearTx as u32 | Control::ClearRx as u32);
+        self.registers.CLK.write(config.divisor() as u32);
+        self.set_mode(config.mode);
+        self.set_chip_select(config.chip_select);
+        self.set_cs_polarity(config.chip_select, config.cs_active_high);
+    }
+
+    /// Sets the clock polarity and phase.
+    pub fn set_mode(&mut self, mode: Mode) {
+        let cs = self.registers.CS.read() & !(Control::Cpol as u32 | Control::Cpha as u32);
+        self.registers.CS.write(cs | mode.bits());
+    }
+
+    /// Selects the chip select line asserted during transfers.
+    pub fn set_chip_select(&mut self, line: ChipSelect) {
+        let cs = self.registers.CS.read() & !(Control::ChipSelect as u32);
+        self.registers.CS.write(cs | line as u32);
+    }
+
+    /// Sets whether `line` is asserted high (`true`) or low (`false`).
+    pub fn set_cs_polarity(&mut self, line: ChipSelect, active_high: bool) {
+        let mask = 1 << (CS_POLARITY_SHIFT + line as u32);
+        if active_high {
+            self.registers.CS.or_mask(mask);
+        } else {
+            self.registers.CS.and_mask(!mask);
+        }
+    }
+
+    /// Sets the clock divisor. See `SpiConfig::divisor()`.
+    pub fn set_divisor(&mut self, divisor: u16) {
+        self.registers.CLK.write(divisor as u32);
+    }
+
+    /// Clears both FIFOs and asserts the chip select.
+    fn begin(&mut self) {
+        self.registers.CS.or_mask(Control::ClearTx as u32 | Control::ClearRx as u32);
+        self.registers.CS.or_mask(Control::TransferActive as u32);
+    }
+
+    /// Waits for the last byte to be shifted out and deasserts the chip
+    /// select.
+    fn end(&mut self) {
+        while !self.registers.CS.has_mask(Control::Done as u32) { }
+        self.registers.CS.and_mask(!(Control::TransferActive as u32));
+    }
+
+    /// Deasserts the chip select without waiting for the transfer to finish,
+    /// and discards what's left in both FIFOs.
+    fn abort(&mut self) {
+        self.registers.CS.and_mask(!(Control::TransferActive as u32));
+        self.registers.CS.or_mask(Control::ClearTx as u32 | Control::ClearRx as u32);
+    }
+
+    /// Shifts out `len` bytes, taking byte `i` from `tx(i)` and handing the
+    /// byte received at the same time to `rx(i, byte)`.
+    fn exchange<T, R>(&mut self, len: usize, tx: T, mut rx: R)
+        where T: Fn(usize) -> u8, R: FnMut(usize, u8)
+    {
+        self.begin();
+        let (mut sent, mut received) = (0, 0);
+        while received < len {
+            while sent < len && self.registers.CS.has_mask(Control::TxSpace as u32) {
+                self.registers.FIFO.write(tx(sent) as u32);
+                sent += 1;
+            }
+
+            while received < sent && self.registers.CS.has_mask(Control::RxData as u32) {
+                rx(received, self.registers.FIFO.read() as u8);
+                received += 1;
+            }
+        }
+        self.end();
+    }
+
+    /// Sends `tx` while receiving the same number of bytes into `rx`, with
+    /// the chip select asserted throughout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tx` and `rx` differ in length.
+    pub fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) {
+        assert_eq!(tx.len(), rx.len(), "Spi::transfer(): lengths differ");
+        self.exchange(tx.len(), |i| tx[i], |i, byte| rx[i] = byte);
+    }
+
+    /// Sends `buf`, replacing each byte with the one received at the same
+    /// time.
+    pub fn transfer_in_place(&mut self, buf: &mut [u8]) {
+        // Byte `i` is always sent before byte `i` is received.
+        let ptr = buf.as_mut_ptr();
+        self.exchange(buf.len(), |i| unsafe { *ptr.add(i) }, |i, byte| unsafe { *ptr.add(i) = byte });
+    }
+
+    /// Sends `buf`, discarding the bytes received.
+    pub fn write(&mut self, buf: &[u8]) {
+        self.exchange(buf.len(), |i| buf[i], |_, _| {});
+    }
+
+    /// Fills `buf` with bytes received while sending `fill`.
+    pub fn read(&mut self, fill: u8, buf: &mut [u8]) {
+        self.exchange(buf.len(), |_| fill, |i, byte| buf[i] = byte);
+    }
+
+    /// Sends `tx` while receiving the same number of words into `rx`, using
+    /// DMA channels `tx_channel` and `rx_channel`. Each word carries four
+    /// bytes, least significant first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either channel can't run its transfer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tx` and `rx` differ in length or hold more than 65535 bytes.
+    pub fn transfer_dma(&mut self, tx_channel: &mut dma::Channel, rx_channel: &mut dma::Channel,
+                        tx: &[u32], rx: &mut [u32]) -> Result<(), dma::Error> {
+        assert_eq!(tx.len(), rx.len(), "Spi::transfer_dma(): lengths differ");
+        assert!(tx.len() * 4 <= 0xFFFF, "Spi::transfer_dma(): too long");
+
+        self.registers.DLEN.write((tx.len() * 4) as u32);
+        self.registers.CS.or_mask(Control::DmaEnable as u32 | Control::AutoDeassert as u32);
+        self.begin();
+
+        let mut rx_blocks = [ControlBlock::from_peripheral(Peripheral::SpiRx, rx)];
+        let mut tx_blocks = [ControlBlock::to_peripheral(tx, Peripheral::SpiTx)];
+
+        // Receiving must be underway before the first byte goes out. The
+        // receive is waited on or dropped, which aborts it, before
+        // `rx_blocks` goes out of scope. If sending fails, the bytes still to
+        // be received will never arrive, so the receive is dropped unwaited.
+        let result = unsafe { rx_channel.start(&mut rx_blocks) }
+            .and_then(|receive| tx_channel.run(&mut tx_blocks).and_then(move |_| receive.wait()));
+
+        if result.is_ok() {
+            self.end();
+        } else {
+            self.abort();
+        }
+        self.registers.CS.and_mask(!(Control::DmaEnable as u32 | Control::AutoDeassert as u32));
+        result
+    }
+}
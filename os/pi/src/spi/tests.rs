@@ -0,0 +1,79 @@
+use test_util::MockRegs;
+
+use super::{ChipSelect, Mode, Spi, SpiConfig};
+
+/// Word offsets of the SPI registers used below.
+const CS: usize = 0;
+const CLK: usize = 2;
+
+/// `CS` bits checked below.
+const CHIP_SELECT: u32 = 0b11;
+const CPHA: u32 = 1 << 2;
+const CPOL: u32 = 1 << 3;
+const CSPOL0: u32 = 1 << 21;
+const CSPOL1: u32 = 1 << 22;
+
+fn config(frequency: u32) -> SpiConfig {
+    SpiConfig { core_clock: 250_000_000, frequency: frequency, ..SpiConfig::default() }
+}
+
+#[test]
+fn divisor() {
+    assert_eq!(config(1_000_000).divisor(), 250);
+    assert_eq!(config(1_000_000).actual_frequency(), 1_000_000);
+
+    // Rounded up to the next even divisor, so never faster than requested.
+    assert_eq!(config(3_000_000).divisor(), 84);
+    assert!(config(3_000_000).actual_frequency() <= 3_000_000);
+
+    assert_eq!(config(250_000_000).divisor(), 2);
+    assert_eq!(config(0).divisor(), 0);
+    assert_eq!(config(0).actual_frequency(), 250_000_000 / 0x10000);
+}
+
+#[test]
+fn configure() {
+    let mut regs = MockRegs::<6>::new();
+    regs[CS] = CPOL | CSPOL0;
+    let mut spi = unsafe { Spi::at(regs.base()) };
+
+    spi.configure(&SpiConfig {
+        mode: Mode::Mode1,
+        chip_select: ChipSelect::One,
+        cs_active_high: true,
+        ..config(1_000_000)
+    });
+    assert_eq!(regs[CLK], 250);
+    assert_eq!(regs[CS] & (CPOL | CPHA), CPHA);
+    assert_eq!(regs[CS] & CHIP_SELECT, 1);
+    assert_eq!(regs[CS] & (CSPOL0 | CSPOL1), CSPOL1);
+}
+
+#[test]
+fn modes() {
+    let mut regs = MockRegs::<6>::new();
+    regs[CS] = CHIP_SELECT | CSPOL0;
+    let mut spi = unsafe { Spi::at(regs.base()) };
+
+    let expected = [(Mode::Mode0, 0), (Mode::Mode1, CPHA), (Mode::Mode2, CPOL), (Mode::Mode3, CPOL | CPHA)];
+    for &(mode, bits) in expected.iter() {
+        spi.set_mode(mode);
+        assert_eq!(regs[CS], CHIP_SELECT | CSPOL0 | bits);
+    }
+
+    spi.set_chip_select(ChipSelect::Zero);
+    assert_eq!(regs[CS], CSPOL0 | CPOL | CPHA);
+}
+
+#[test]
+fn cs_polarity() {
+    let regs = MockRegs::<6>::new();
+    let mut spi = unsafe { Spi::at(regs.base()) };
+
+    spi.set_cs_polarity(ChipSelect::Zero, true);
+    spi.set_cs_polarity(ChipSelect::One, true);
+    assert_eq!(regs[CS], CSPOL0 | CSPOL1);
+
+    spi.set_cs_polarity(ChipSelect::Zero, false);
+    assert_eq!(regs[CS], CSPOL1);
+}
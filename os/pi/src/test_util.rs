@@ -0,0 +1,43 @@
+use core::ops::{Index, IndexMut};
+
+use std::vec::Vec;
+
+/// A zeroed block of `N` 32-bit registers in ordinary memory, for running a
+/// driver's `at()` handle in unit tests.
+///
+/// Every block is a fresh, leaked allocation: it outlives the `'static`
+/// register reference the driver holds, and tests running concurrently never
+/// share one. Registers are indexed by word offset.
+pub struct MockRegs<const N: usize> {
+    words: *mut u32,
+}
+
+impl<const N: usize> MockRegs<N> {
+    /// Returns a new block with every register zeroed.
+    pub fn new() -> MockRegs<N> {
+        let mut words = Vec::with_capacity(N);
+        words.resize(N, 0u32);
+        MockRegs { words: words.leak().as_mut_ptr() }
+    }
+
+    /// Returns the address of the block, to be passed to a driver's `at()`.
+    pub fn base(&self) -> usize {
+        self.words as usize
+    }
+}
+
+impl<const N: usize> Index<usize> for MockRegs<N> {
+    type Output = u32;
+
+    fn index(&self, offset: usize) -> &u32 {
+        assert!(offset < N, "MockRegs: offset {} out of bounds", offset);
+        unsafe { &*self.words.add(offset) }
+    }
+}
+
+impl<const N: usize> IndexMut<usize> for MockRegs<N> {
+    fn index_mut(&mut self, offset: usize) -> &mut u32 {
+        assert!(offset < N, "MockRegs: offset {} out of bounds", offset);
+        unsafe { &mut *self.words.add(offset) }
+    }
+}
@@ -0,0 +1,154 @@
+use common::IO_BASE;
+use interrupt::{Controller, Interrupt};
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile};
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the ARM system timer registers.
+const TIMER_REG_BASE: usize = IO_BASE + 0x3000;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CS: Volatile<u32>,
+    CLO: ReadVolatile<u32>,
+    CHI: ReadVolatile<u32>,
+    COMPARE: [Volatile<u32>; 4]
+}
+
+/// A compare channel of the system timer.
+///
+/// Channels 0 and 2 are used by the GPU firmware, so only channels 1 and 3 are
+/// available to the ARM core.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    One = 1,
+    Three = 3,
+}
+
+impl Channel {
+    /// Returns the interrupt raised when this channel's compare matches.
+    pub fn interrupt(self) -> Interrupt {
+        match self {
+            Channel::One => Interrupt::Timer1,
+            Channel::Three => Interrupt::Timer3,
+        }
+    }
+
+    /// Returns the mask of this channel's match flag in `CS`.
+    fn mask(self) -> u32 {
+        1 << (self as u8)
+    }
+}
+
+/// The Raspberry Pi ARM system timer.
+pub struct Timer {
+    registers: &'static mut Registers
+}
+
+impl Timer {
+    /// Returns a new instance of `Timer`.
+    pub fn new() -> Timer {
+        unsafe { Timer::at(TIMER_REG_BASE) }
+    }
+
+    /// Returns a handle to system timer registers at `base`. Nothing is
+    /// written, so any number of handles may share a block.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to a `'static` system timer register block.
+    pub(crate) unsafe fn at(base: usize) -> Timer {
+        Timer {
+            registers: &mut *(base as *mut Registers),
+        }
+    }
+
+    /// Reads the system timer's counter and returns the 64-bit counter value.
+    /// The returned value is the number of elapsed microseconds.
+    pub fn read(&self) -> u64 {
+        let low = self.registers.CLO.read();
+        let high = self.registers.CHI.read();
+        let cat = ((high as u64) << 32) | (low as u64);
+        return cat;
+    }
+
+    /// Arms `channel` to match when the counter reaches `deadline`
+    /// microseconds. Any earlier match on `channel` is cleared.
+    ///
+    /// Only the low 32 bits of the counter are compared, so `deadline` must be
+    /// less than ~71 minutes away. Returns `false` without arming the channel
+    /// if `deadline` has already passed.
+    pub fn arm_at(&mut self, channel: Channel, deadline: u64) -> bool {
+        if deadline <= self.read() {
+            return false;
+        }
+
+        self.clear_match(channel);
+        self.registers.COMPARE[channel as usize].write(deadline as u32);
+        true
+    }
+
+    /// Arms `channel` to match `us` microseconds from now. Any earlier match
+    /// on `channel` is cleared.
+    pub fn arm_in(&mut self, channel: Channel, us: u32) {
+        self.clear_match(channel);
+        let now = self.registers.CLO.read();
+        self.registers.COMPARE[channel as usize].write(now.wrapping_add(us));
+    }
+
+    /// Returns `true` if `channel`'s compare has matched since its flag was
+    /// last cleared.
+    pub fn is_matched(&self, channel: Channel) -> bool {
+        self.registers.CS.has_mask(channel.mask())
+    }
+
+    /// Clears `channel`'s match flag, which also acknowledges its interrupt.
+    pub fn clear_match(&mut self, channel: Channel) {
+        // `CS` is write-one-to-clear: writing zero bits leaves other channels
+        // untouched.
+        self.registers.CS.write(channel.mask());
+    }
+
+    /// Routes `channel`'s match interrupt through the interrupt controller.
+    pub fn enable_interrupt(&mut self, channel: Channel) {
+        Controller::new().enable(channel.interrupt());
+    }
+
+    /// Stops routing `channel`'s match interrupt through the interrupt
+    /// controller.
+    pub fn disable_interrupt(&mut self, channel: Channel) {
+        Controller::new().disable(channel.interrupt());
+    }
+}
+
+/// Returns the current time in microseconds.
+pub fn current_time() -> u64 {
+    Timer::new().read()
+}
+
+/// Sets up a match in timer channel 1 to occur `us` microseconds from now. If
+/// interrupts for timer channel 1 are enabled and IRQs are unmasked, a timer
+/// interrupt will be issued in `us` microseconds.
+pub fn tick_in(us: u32) {
+    Timer::new().arm_in(Channel::One, us);
+}
+
+/// Spins until `us` microseconds have passed.
+pub fn spin_sleep_us(us: u64) {
+    let old_time = current_time();
+    loop {
+        let cur_time = current_time();
+        if cur_time - old_time >= us {
+            break;
+        }
+    }
+}
+
+/// Spins until `ms` milliseconds have passed.
+pub fn spin_sleep_ms(ms: u64) {
+    spin_sleep_us(ms * 1000);
+}
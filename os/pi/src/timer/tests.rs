@@ -0,0 +1,83 @@
+use test_util::MockRegs;
+
+use super::{Channel, Timer};
+
+/// Word offsets of the system timer registers.
+const CS: usize = 0;
+const CLO: usize = 1;
+const CHI: usize = 2;
+const COMPARE: usize = 3;
+
+/// `CS` match flags.
+const M0: u32 = 1 << 0;
+const M1: u32 = 1 << 1;
+const M3: u32 = 1 << 3;
+
+#[test]
+fn arm_at() {
+    let mut regs = MockRegs::<7>::new();
+    regs[CLO] = 1000;
+    let mut timer = unsafe { Timer::at(regs.base()) };
+
+    assert!(timer.arm_at(Channel::One, 1500));
+    assert_eq!(regs[COMPARE + 1], 1500);
+    assert_eq!(regs[CS], M1);
+
+    // Only the low 32 bits of the deadline are compared.
+    regs[CLO] = 5;
+    regs[CHI] = 1;
+    assert!(timer.arm_at(Channel::Three, 1 << 32 | 100));
+    assert_eq!(regs[COMPARE + 3], 100);
+    assert_eq!(regs[CS], M3);
+}
+
+#[test]
+fn arm_at_passed() {
+    let mut regs = MockRegs::<7>::new();
+    regs[CS] = M1;
+    regs[CLO] = 1000;
+    regs[COMPARE + 1] = 7;
+    let mut timer = unsafe { Timer::at(regs.base()) };
+
+    // Neither the compare register nor the match flag is touched.
+    assert!(!timer.arm_at(Channel::One, 1000));
+    assert!(!timer.arm_at(Channel::One, 999));
+    assert_eq!(regs[COMPARE + 1], 7);
+    assert_eq!(regs[CS], M1);
+}
+
+#[test]
+fn arm_in() {
+    let mut regs = MockRegs::<7>::new();
+    regs[CLO] = 1000;
+    let mut timer = unsafe { Timer::at(regs.base()) };
+
+    timer.arm_in(Channel::Three, 250);
+    assert_eq!(regs[COMPARE + 3], 1250);
+    assert_eq!(regs[CS], M3);
+
+    // The compare register wraps with the low word of the counter.
+    regs[CLO] = 0xFFFF_FF00;
+    timer.arm_in(Channel::One, 0x200);
+    assert_eq!(regs[COMPARE + 1], 0x100);
+    assert_eq!(regs[CS], M1);
+}
+
+#[test]
+fn match_flags() {
+    let mut regs = MockRegs::<7>::new();
+    regs[CS] = M0 | M1 | M3;
+    let mut timer = unsafe { Timer::at(regs.base()) };
+
+    assert!(timer.is_matched(Channel::One));
+    assert!(timer.is_matched(Channel::Three));
+
+    // `CS` is write-one-to-clear, so only the channel's own bit is written;
+    // writing the others back would clear them too.
+    timer.clear_match(Channel::Three);
+    assert_eq!(regs[CS], M3);
+
+    regs[CS] = M0;
+    assert!(!timer.is_matched(Channel::One));
+    assert!(!timer.is_matched(Channel::Three));
+}
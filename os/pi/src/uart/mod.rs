@@ -0,0 +1,360 @@
+use core::fmt;
+
+use volatile::prelude::*;
+use volatile::{Volatile, ReadVolatile, Reserved};
+
+use timer;
+use common::IO_BASE;
+use gpio::{Alt, Gpio, Function};
+
+#[cfg(test)]
+mod tests;
+
+/// The base address for the `MU` registers.
+const MU_REG_BASE: usize = IO_BASE + 0x215040;
+
+/// The `AUXENB` register from page 9 of the BCM2837 documentation.
+const AUX_ENABLES: *mut Volatile<u8> = (IO_BASE + 0x215004) as *mut Volatile<u8>;
+
+/// Enum representing bit fields of the `AUX_MU_LSR_REG` register.
+#[repr(u8)]
+enum LsrStatus {
+    DataReady = 1,
+    RxOverrun = 1 << 1,
+    TxAvailable = 1 << 5,
+    TxIdle = 1 << 6,
+}
+
+/// The VPU core clock the mini UART runs from when `enable_uart=1` is set in
+/// `config.txt`, in Hz.
+pub const DEFAULT_CORE_CLOCK: u32 = 250_000_000;
+
+/// Number of data bits per character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataBits {
+    Seven,
+    Eight,
+}
+
+/// Configuration for the mini UART.
+#[derive(Debug, Copy, Clone)]
+pub struct MiniUartConfig {
+    /// Frequency of the VPU core clock, in Hz. The mini UART's baud rate is
+    /// derived from it, so it must match the firmware's `core_freq`.
+    pub core_clock: u32,
+    /// Requested baud rate.
+    pub baud_rate: u32,
+    /// Number of data bits per character.
+    pub data_bits: DataBits,
+    /// Read timeout in milliseconds, or `None` for reads that never time out.
+    pub read_timeout: Option<u32>,
+}
+
+impl Default for MiniUartConfig {
+    /// 115200 baud, 8 data bits, no read timeout, at the default core clock.
+    fn default() -> MiniUartConfig {
+        MiniUartConfig {
+            core_clock: DEFAULT_CORE_CLOCK,
+            baud_rate: 115200,
+            data_bits: DataBits::Eight,
+            read_timeout: None,
+        }
+    }
+}
+
+impl MiniUartConfig {
+    /// Returns the value for `AUX_MU_BAUD` that gets closest to the requested
+    /// baud rate, from `baud_rate = core_clock / (8 * (divisor + 1))`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baud rate is `0` or can't be reached with a 16-bit
+    /// divisor.
+    pub fn divisor(&self) -> u16 {
+        let step = 8 * self.baud_rate as u64;
+        let divisor = (self.core_clock as u64 + step / 2) / step.max(1);
+        if self.baud_rate == 0 || divisor == 0 || divisor > 0x10000 {
+            panic!("MiniUartConfig: baud rate {} unreachable from a {} Hz clock",
+                   self.baud_rate, self.core_clock);
+        }
+
+        (divisor - 1) as u16
+    }
+
+    /// Returns the baud rate actually achieved by `divisor()`.
+    pub fn actual_baud_rate(&self) -> u32 {
+        self.core_clock / (8 * (self.divisor() as u32 + 1))
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // FIXME: Declare the "MU" registers from page 8.
+    AUX_MU_IO_REG: Volatile<u8>,
+    __r0: [Reserved<u8>; 3],
+    AUX_MU_IER_REG: Volatile<u8>,
+    __r1: [Reserved<u8>; 3],
+    AUX_MU_IIR_REG: Volatile<u8>,
+    __r2: [Reserved<u8>; 3],
+    AUX_MU_LCR_REG: Volatile<u8>,
+    __r3: [Reserved<u8>; 3],
+    AUX_MU_MCR_REG: Volatile<u8>,
+    __r4: [Reserved<u8>; 3],
+    AUX_MU_LSR_REG: ReadVolatile<u8>,
+    __r5: [Reserved<u8>; 3],
+    AUX_MU_MSR_REG: ReadVolatile<u8>,
+    __r6: [Reserved<u8>; 3],
+    AUX_MU_SCRATCH: Volatile<u8>,
+    __r7: [Reserved<u8>; 3],
+    AUX_MU_CNTL_REG: Volatile<u8>,
+    __r8: [Reserved<u8>; 3],
+    AUX_MU_STAT_REG: ReadVolatile<u32>,
+    AUX_MU_BAUD: Volatile<u16>
+}
+
+/// The Raspberry Pi's "mini UART".
+pub struct MiniUart {
+    registers: &'static mut Registers,
+    timeout: Option<u32>,
+    /// GPIO pins 14 and 15, returned when the UART is dropped. Handles that
+    /// didn't configure the UART don't hold them.
+    _pins: Option<[Gpio<Alt>; 2]>,
+}
+
+impl MiniUart {
+    /// Initializes the mini UART with the default configuration: 8-bit data
+    /// and ~115200 baud (a divisor of 270 at a 250MHz core clock). See
+    /// `with_config()`.
+    ///
+    /// By default, reads will never time out. To set a read timeout, use
+    /// `set_read_timeout()`.
+    pub fn new() -> MiniUart {
+        MiniUart::with_config(MiniUartConfig::default())
+    }
+
+    /// Initializes the mini UART by enabling it as an auxiliary peripheral,
+    /// setting the data size and baud rate divisor from `config`, setting GPIO
+    /// pins 14 and 15 to alternative function 5 (TXD1/RDXD1), and finally
+    /// enabling the UART transmitter and receiver.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config`'s baud rate can't be reached (see
+    /// `MiniUartConfig::divisor()`) or if GPIO pins 14 or 15 are already in
+    /// use, for instance by the PL011 UART or another `MiniUart`. The pins
+    /// are returned when the `MiniUart` is dropped.
+    pub fn with_config(config: MiniUartConfig) -> MiniUart {
+        // Claim the pins first, so a UART in use isn't disturbed on failure.
+        let (tx, rx) = (Gpio::new(14), Gpio::new(15));
+        let registers = unsafe {
+            // Enable the mini UART as an auxiliary device.
+            (*AUX_ENABLES).or_mask(1);
+            &mut *(MU_REG_BASE as *mut Registers)
+        };
+
+        // Keep the transmitter and receiver off while reconfiguring.
+        registers.AUX_MU_CNTL_REG.write(0);
+
+        // GPIO 14 15 set to TX/RX
+        let pins = [tx.into_alt(Function::Alt5), rx.into_alt(Function::Alt5)];
+
+        // Bit 1 is undocumented but required for 8-bit mode.
+        registers.AUX_MU_LCR_REG.write(match config.data_bits {
+            DataBits::Seven => 0b00,
+            DataBits::Eight => 0b11,
+        });
+        registers.AUX_MU_BAUD.write(config.divisor()); // 16-bit baudrate register
+        registers.AUX_MU_CNTL_REG.write(0b11); // enable TX & RX
+
+        MiniUart {
+            registers: registers,
+            timeout: config.read_timeout,
+            _pins: Some(pins),
+        }
+    }
+
+    /// Returns a handle to the mini UART without reconfiguring it, for use in
+    /// interrupt handlers.
+    ///
+    /// # Safety
+    ///
+    /// The mini UART must already have been initialized with `new()` or
+    /// `with_config()`, and the caller must not race with another handle's
+    /// use of the same registers.
+    pub unsafe fn from_initialized() -> MiniUart {
+        MiniUart::at(MU_REG_BASE)
+    }
+
+    /// Returns a handle to mini UART registers at `base` without configuring
+    /// them or claiming GPIO pins. Reads through the handle never time out.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to a `'static` mini UART register block.
+    pub(crate) unsafe fn at(base: usize) -> MiniUart {
+        MiniUart {
+            registers: &mut *(base as *mut Registers),
+            timeout: None,
+            _pins: None,
+        }
+    }
+
+    /// Enables or disables the receive interrupt, which is asserted while the
+    /// receive FIFO holds at least one byte. The interrupt is routed to the
+    /// interrupt controller as `Interrupt::Aux`.
+    pub fn set_rx_interrupt(&mut self, enabled: bool) {
+        // Bit 0 enables the receive interrupt. Per the BCM2835 errata, bits
+        // 3:2 must also be set or the mini UART raises no interrupts at all.
+        if enabled {
+            self.registers.AUX_MU_IER_REG.or_mask(0b1101);
+        } else {
+            self.registers.AUX_MU_IER_REG.and_mask(!0b1101);
+        }
+    }
+
+    /// Returns `true` if bytes were dropped because the receive FIFO was full.
+    ///
+    /// The flag is cleared whenever the line status is read, including by
+    /// `has_byte()`, so this is only reliable when called first.
+    pub fn rx_overrun(&self) -> bool {
+        self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::RxOverrun as u8)
+    }
+
+    /// Set the read timeout to `milliseconds` milliseconds.
+    pub fn set_read_timeout(&mut self, milliseconds: u32) {
+        self.timeout = Some(milliseconds)
+    }
+
+    /// Write the byte `byte`. This method blocks until there is space available
+    /// in the output FIFO.
+    pub fn write_byte(&mut self, byte: u8) {
+        while !self.can_write() { };
+        self.registers.AUX_MU_IO_REG.write(byte);
+    }
+
+    /// Returns `true` if there is space in the output FIFO. If this method
+    /// returns `true`, a subsequent call to `write_byte` is guaranteed to
+    /// return immediately. This method does not block.
+    pub fn can_write(&self) -> bool {
+        self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::TxAvailable as u8)
+    }
+
+    /// Returns `true` if the output FIFO is empty and the last byte has been
+    /// shifted out. This method does not block.
+    pub fn is_idle(&self) -> bool {
+        self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::TxIdle as u8)
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read. If this
+    /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately. This method does not block.
+    pub fn has_byte(&self) -> bool {
+        self.registers.AUX_MU_LSR_REG.has_mask(LsrStatus::DataReady as u8)
+    }
+
+    /// Blocks until there is a byte ready to read. If a read timeout is set,
+    /// this method blocks for at most that amount of time. Otherwise, this
+    /// method blocks indefinitely until there is a byte to read.
+    ///
+    /// Returns `Ok(())` if a byte is ready to read. Returns `Err(())` if the
+    /// timeout expired while waiting for a byte to be ready. If this method
+    /// returns `Ok(())`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        match self.timeout {
+            None => {
+                loop {
+                    if self.has_byte() {
+                        return Ok(())
+                    }
+                }
+            },
+            Some(timeout) => {
+                let old = timer::current_time();
+                let deadline = old + (timeout as u64) * 1000; // Internal clock unit is microsecond, but timeout unit is millisecond
+                while deadline >= timer::current_time() {
+                    if self.has_byte() {
+                        return Ok(())
+                    }
+                }
+                Err(())
+            }
+        }
+    }
+
+    /// Reads a byte. Blocks indefinitely until a byte is ready to be read.
+    pub fn read_byte(&mut self) -> u8 {
+        while !self.has_byte() { }
+        self.registers.AUX_MU_IO_REG.read()
+    }
+
+    /// Blocks until the transmit FIFO is empty and the last byte has been
+    /// shifted out.
+    pub fn flush(&mut self) {
+        while !self.is_idle() { }
+    }
+}
+
+// FIXME: Implement `fmt::Write` for `MiniUart`. A b'\r' byte should be written
+// before writing any b'\n' byte.
+impl fmt::Write for MiniUart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            match byte {
+                b'\n' => {
+                    self.write_byte(b'\r');
+                    self.write_byte(b'\n');
+                },
+                _ => self.write_byte(byte)
+            }
+        }
+        Ok(())
+    }
+}
+
+mod uart_io {
+    use std::io;
+    use super::MiniUart;
+
+    // FIXME: Implement `io::Read` and `io::Write` for `MiniUart`.
+    //
+    // The `io::Read::read()` implementation must respect the read timeout by
+    // waiting at most that time for the _first byte_. It should not wait for
+    // any additional bytes but _should_ read as many bytes as possible. If the
+    // read times out, an error of kind `TimedOut` should be returned.
+    //
+    // The `io::Write::write()` method must write all of the requested bytes
+    // before returning.
+    impl io::Read for MiniUart {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.wait_for_byte() {
+                Ok(()) => {
+                    let mut index = 0;
+                    while self.has_byte() && index < buf.len() {
+                        buf[index] = self.read_byte();
+                        index += 1;
+                    }
+                    Ok(index)
+                },
+                Err(()) => {
+                    Err(io::Error::new(io::ErrorKind::TimedOut, "reading UART timed out"))
+                }
+            }
+        }
+    }
+
+    impl io::Write for MiniUart {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for byte in buf {
+                self.write_byte(*byte);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            MiniUart::flush(self);
+            Ok(())
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use super::MiniUartConfig;
+
+fn config(baud_rate: u32) -> MiniUartConfig {
+    MiniUartConfig { core_clock: 250_000_000, baud_rate: baud_rate, ..MiniUartConfig::default() }
+}
+
+#[test]
+fn divisor() {
+    assert_eq!(config(115200).divisor(), 270);
+    assert_eq!(config(115200).actual_baud_rate(), 115313);
+
+    assert_eq!(config(230400).divisor(), 135);
+    assert_eq!(config(230400).actual_baud_rate(), 229779);
+
+    assert_eq!(config(921600).divisor(), 33);
+    assert_eq!(config(921600).actual_baud_rate(), 919117);
+}
+
+#[test]
+fn limits() {
+    // A divisor of 0 gives the fastest rate, and one of 65535 the slowest.
+    assert_eq!(config(31_250_000).divisor(), 0);
+    let slowest = MiniUartConfig { core_clock: 8 * 0x10000 * 100, ..config(100) };
+    assert_eq!(slowest.divisor(), 0xFFFF);
+    assert_eq!(slowest.actual_baud_rate(), 100);
+}
+
+#[test]
+#[should_panic(expected = "unreachable")]
+fn too_fast() {
+    config(100_000_000).divisor();
+}
+
+#[test]
+#[should_panic(expected = "unreachable")]
+fn too_slow() {
+    config(100).divisor();
+}
+
+#[test]
+#[should_panic(expected = "unreachable")]
+fn zero_baud_rate() {
+    config(0).divisor();
+}